@@ -0,0 +1,155 @@
+use crate::flags::{Condition, Flags};
+use crate::storage;
+use crate::ui;
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+// Seconds an achievement's popup stays up
+
+const TOAST_TIME: f32 = 3.0;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Achievement {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub trigger: Condition,
+    // Only counts in this level, puzzle tags get reused between levels
+    #[serde(default)]
+    pub level: Option<String>,
+    // Has to happen within this many seconds of starting the level
+    #[serde(default)]
+    pub within: Option<f32>,
+    // Shown as ??? until earned
+    #[serde(default)]
+    pub secret: bool,
+}
+
+// Every achievement in the game, which ones have ever been earned, and
+// popups for the ones just earned
+
+pub struct Achievements {
+    list: Vec<Achievement>,
+    unlocked: Vec<String>,
+    toasts: Vec<String>,
+    toast_time: f32,
+}
+
+impl Achievements {
+    pub fn new(data: &str) -> Self {
+        let list: Vec<Achievement> = serde_json::from_str(data).unwrap();
+        let unlocked = storage::load("achievements")
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Achievements { list, unlocked, toasts: Vec::new(), toast_time: 0f32 }
+    }
+
+    // Unlocks anything newly earned, handing back their ids
+    pub fn check(&mut self, level: &str, flags: &Flags, elapsed: f32) -> Vec<String> {
+        let earned: Vec<Achievement> = self
+            .list
+            .iter()
+            .filter(|a| !self.unlocked.contains(&a.id))
+            .filter(|a| a.level.as_ref().is_none_or(|l| l == level))
+            .filter(|a| a.within.is_none_or(|within| elapsed <= within))
+            .filter(|a| a.trigger.met(flags))
+            .cloned()
+            .collect();
+        if earned.is_empty() {
+            return Vec::new();
+        }
+        for achievement in &earned {
+            self.unlocked.push(achievement.id.clone());
+            self.toasts.push(achievement.title.clone());
+        }
+        if let Ok(text) = serde_json::to_string(&self.unlocked) {
+            storage::save("achievements", &text);
+        }
+        earned.into_iter().map(|a| a.id).collect()
+    }
+
+    // Popups go one after another
+    pub fn update(&mut self, dt: f32) {
+        if self.toasts.is_empty() {
+            return;
+        }
+        self.toast_time += dt;
+        if self.toast_time >= TOAST_TIME {
+            self.toasts.remove(0);
+            self.toast_time = 0f32;
+        }
+    }
+
+    pub fn draw_toast(&self) {
+        let Some(title) = self.toasts.first() else { return };
+        let alpha = (TOAST_TIME - self.toast_time).clamp(0f32, 1f32);
+        draw_rectangle(470.0, 60.0, 320.0, 70.0, Color::new(0f32, 0f32, 0f32, 0.8 * alpha));
+        ui::text("Achievement unlocked", 485.0, 88.0, 24.0, Color::new(1f32, 0.8, 0f32, alpha));
+        ui::text(title, 485.0, 118.0, 30.0, Color::new(1f32, 1f32, 1f32, alpha));
+    }
+
+    pub fn draw_list(&self) {
+        ui::text("Achievements", 20.0, 60.0, 60.0, YELLOW);
+        let found = self.list.iter().filter(|a| self.unlocked.contains(&a.id)).count();
+        let progress = ui::tr("{} of {} earned")
+            .replacen("{}", &found.to_string(), 1)
+            .replacen("{}", &self.list.len().to_string(), 1);
+        ui::text(&progress, 20.0, 100.0, 30.0, GRAY);
+        for (i, achievement) in self.list.iter().enumerate() {
+            let y = 160.0 + (i as f32 * 60.0);
+            if self.unlocked.contains(&achievement.id) {
+                ui::text(&achievement.title, 40.0, y, 36.0, WHITE);
+                ui::text(&achievement.description, 40.0, y + 25.0, 24.0, GRAY);
+            } else if achievement.secret {
+                draw_text("???", 40.0, y, 36.0, DARKGRAY);
+            } else {
+                ui::text(&achievement.title, 40.0, y, 36.0, DARKGRAY);
+                ui::text(&achievement.description, 40.0, y + 25.0, 24.0, DARKGRAY);
+            }
+        }
+        ui::text("Click to go back", 20.0, 570.0, 30.0, GRAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Whatever's been earned on this machine stays out of it
+    fn achievements() -> Achievements {
+        storage::disable_writes();
+        let mut achievements = Achievements::new(&std::fs::read_to_string("assets/achievements.json").unwrap());
+        achievements.unlocked.clear();
+        achievements
+    }
+
+    fn flags(names: &[&str]) -> Flags {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn earned_once_with_a_popup() {
+        let mut achievements = achievements();
+        let flags = flags(&["solved:door_pad", "wrong:door_pad", "ending:homebody"]);
+        assert_eq!(achievements.check("room", &flags, 10f32), vec!["staying_put"]);
+        assert_eq!(achievements.toasts, vec!["Staying Put"]);
+        assert!(achievements.check("room", &flags, 20f32).is_empty());
+
+        achievements.update(TOAST_TIME);
+        assert!(achievements.toasts.is_empty());
+    }
+
+    #[test]
+    fn levels_and_time_limits_count() {
+        let mut achievements = achievements();
+        assert!(achievements.check("room", &flags(&["ending:escaped"]), 100f32).is_empty());
+        assert_eq!(achievements.check("annex", &flags(&["ending:escaped"]), 400f32), vec!["unaided"]);
+    }
+
+    #[test]
+    fn hints_rule_out_going_unaided() {
+        let mut achievements = achievements();
+        let earned = achievements.check("annex", &flags(&["ending:escaped", "hint:door_pad", "hinted"]), 100f32);
+        assert_eq!(earned, vec!["quick_exit"]);
+    }
+}
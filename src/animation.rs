@@ -0,0 +1,173 @@
+use crate::assets::Assets;
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Frames cut out of one texture, left to right then top to bottom
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SheetData {
+    pub texture: String,
+    pub frame_size: [f32; 2],
+    pub count: usize,
+}
+
+// A named animation on an item. Frames are separate textures or come off a
+// sheet, and offsets nudge the item around per frame (a ringing phone
+// shaking needs nothing else)
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ClipData {
+    #[serde(default)]
+    pub frames: Vec<String>,
+    #[serde(default)]
+    pub sheet: Option<SheetData>,
+    #[serde(default)]
+    pub offsets: Vec<[f32; 2]>,
+    pub fps: f32,
+    // One shot clips stop on their last frame, like a door left open
+    #[serde(default, rename = "loop")]
+    pub looping: bool,
+}
+
+// Starts a clip whenever a story flag gets set, like a puzzle being solved
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Trigger {
+    pub flag: String,
+    pub play: String,
+}
+
+#[derive(Clone, Copy)]
+struct Frame {
+    texture: Texture2D,
+    // Part of the texture to draw, all of it if none
+    source: Option<Rect>,
+}
+
+#[derive(Clone)]
+struct Clip {
+    frames: Vec<Frame>,
+    offsets: Vec<Vec2>,
+    fps: f32,
+    looping: bool,
+}
+
+impl Clip {
+    fn new(data: &ClipData, assets: &Assets) -> Result<Self, String> {
+        let mut frames = data
+            .frames
+            .iter()
+            .map(|name| Ok(Frame { texture: assets.texture(name)?, source: None }))
+            .collect::<Result<Vec<Frame>, String>>()?;
+        if let Some(sheet) = &data.sheet {
+            let texture = assets.texture(&sheet.texture)?;
+            let [w, h] = sheet.frame_size;
+            let columns = ((texture.width() / w) as usize).max(1);
+            frames.extend((0..sheet.count).map(|i| Frame {
+                texture,
+                source: Some(Rect::new((i % columns) as f32 * w, (i / columns) as f32 * h, w, h)),
+            }));
+        }
+        Ok(Clip {
+            frames,
+            offsets: data.offsets.iter().map(|[x, y]| vec2(*x, *y)).collect(),
+            fps: data.fps,
+            looping: data.looping,
+        })
+    }
+
+    fn length(&self) -> usize {
+        self.frames.len().max(self.offsets.len()).max(1)
+    }
+
+    fn duration(&self) -> f32 {
+        self.length() as f32 / self.fps
+    }
+}
+
+// Plays one clip at a time for an item, timed in seconds so it runs the same
+// at any frame rate
+
+#[derive(Clone)]
+pub struct Animator {
+    clips: HashMap<String, Clip>,
+    playing: Option<String>,
+    time: f32,
+    pub triggers: Vec<Trigger>,
+}
+
+impl Animator {
+    pub fn new(clips: &HashMap<String, ClipData>, triggers: &[Trigger], assets: &Assets) -> Result<Self, String> {
+        Ok(Animator {
+            clips: clips
+                .iter()
+                .map(|(name, data)| Ok((name.clone(), Clip::new(data, assets)?)))
+                .collect::<Result<_, String>>()?,
+            playing: None,
+            time: 0f32,
+            triggers: triggers.to_vec(),
+        })
+    }
+
+    // Just the one clip, playing from the start, for things that aren't items
+    // like a puzzle's pieces
+    pub fn looping(data: &ClipData, assets: &Assets) -> Result<Self, String> {
+        let clips = HashMap::from([(String::new(), data.clone())]);
+        let mut animator = Animator::new(&clips, &[], assets)?;
+        animator.play("");
+        Ok(animator)
+    }
+
+    pub fn play(&mut self, clip: &str) {
+        if !self.clips.contains_key(clip) {
+            eprintln!("No animation {} to play", clip);
+            return;
+        }
+        self.playing = Some(clip.to_string());
+        self.time = 0f32;
+    }
+
+    // Straight to the end, for one shot clips that already happened before
+    // a save was loaded
+    pub fn finish(&mut self, clip: &str) {
+        self.play(clip);
+        if let Some(clip) = self.clips.get(clip) {
+            self.time = clip.duration();
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if self.playing.is_some() {
+            self.time += dt;
+        }
+    }
+
+    // Frame to draw right now, a one shot clip holds its last frame once done
+    fn current(&self) -> Option<(&Clip, usize)> {
+        let clip = self.clips.get(self.playing.as_ref()?)?;
+        let frame = (self.time * clip.fps) as usize;
+        let frame = match clip.looping {
+            true => frame % clip.length(),
+            false => frame.min(clip.length() - 1),
+        };
+        Some((clip, frame))
+    }
+
+    // Draws in place of the item's own texture, which it falls back on when
+    // nothing's playing or the clip only moves it around
+    pub fn draw(&self, texture: Texture2D, at: Vec2) {
+        let Some((clip, frame)) = self.current() else {
+            draw_texture(texture, at.x, at.y, WHITE);
+            return;
+        };
+        let at = at + clip.offsets.get(frame).copied().unwrap_or_default();
+        match clip.frames.get(frame) {
+            Some(Frame { texture, source }) => {
+                let params = DrawTextureParams { source: *source, ..Default::default() };
+                draw_texture_ex(*texture, at.x, at.y, WHITE, params);
+            }
+            None => draw_texture(texture, at.x, at.y, WHITE),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::level::LevelData;
+use macroquad::miniquad;
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+// Textures every level uses, by file name in assets/ without the extension.
+// Everything else comes and goes with the level that lists it
+
+const TEXTURES: &[&str] = &["ArrowLeft", "ArrowRight"];
+
+// Data files, by file name in assets/ without the .json
+
+const DATA: &[&str] = &["endings", "achievements", "campaign", "lang/es"];
+
+#[derive(Clone)]
+pub struct Assets {
+    textures: HashMap<String, Texture2D>,
+    data: HashMap<String, String>,
+    headless: bool,
+    level: Option<(String, LevelData)>,
+}
+
+// Without a graphics context we only need sizes for hit testing, which
+// can be read straight out of the PNG header
+
+fn headless_texture(name: &str) -> Result<Texture2D, String> {
+    let bytes = std::fs::read(format!("assets/{}.png", name)).map_err(|e| format!("{}.png: {}", name, e))?;
+    if bytes.len() < 24 {
+        return Err(format!("{}.png isn't a PNG", name));
+    }
+    let mut texture = miniquad::Texture::empty();
+    texture.width = u32::from_be_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+    texture.height = u32::from_be_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
+    Ok(Texture2D::from_miniquad_texture(texture))
+}
+
+impl Assets {
+    pub async fn load() -> Self {
+        let mut textures = HashMap::new();
+        for name in TEXTURES {
+            let texture = load_texture(&format!("assets/{}.png", name)).await.unwrap();
+            textures.insert(name.to_string(), texture);
+        }
+        let mut data = HashMap::new();
+        for name in DATA {
+            let text = load_string(&format!("assets/{}.json", name)).await.unwrap();
+            data.insert(name.to_string(), text);
+        }
+        Assets { textures, data, headless: false, level: None }
+    }
+    pub fn load_headless() -> Self {
+        let mut textures = HashMap::new();
+        for name in TEXTURES {
+            textures.insert(name.to_string(), headless_texture(name).unwrap());
+        }
+        let mut data = HashMap::new();
+        for name in DATA {
+            let text = std::fs::read_to_string(format!("assets/{}.json", name)).unwrap();
+            data.insert(name.to_string(), text);
+        }
+        Assets { textures, data, headless: true, level: None }
+    }
+
+    // Swaps in a level's textures and puzzle data, anything the last level
+    // had that this one doesn't gets freed. Everything is read before the
+    // swap, so a level that doesn't load leaves the last one as it was
+
+    pub async fn load_level(&mut self, id: &str) -> Result<(), String> {
+        let text = load_string(&format!("assets/levels/{}.json", id)).await.map_err(|e| e.to_string())?;
+        let level: LevelData = serde_json::from_str(&text).map_err(|e| format!("{}: {}", id, e))?;
+        let mut textures = HashMap::new();
+        for name in level.textures.iter().filter(|name| !self.textures.contains_key(*name)) {
+            let texture = load_texture(&format!("assets/{}.png", name)).await.map_err(|e| e.to_string())?;
+            textures.insert(name.to_string(), texture);
+        }
+        let mut data = HashMap::new();
+        for puzzle in &level.puzzles {
+            let path = format!("assets/levels/{}/{}.json", id, puzzle.tag);
+            data.insert(puzzle.tag.clone(), load_string(&path).await.map_err(|e| e.to_string())?);
+        }
+        self.swap_level(id, level, textures, data);
+        Ok(())
+    }
+    pub fn load_level_headless(&mut self, id: &str) -> Result<(), String> {
+        let path = format!("assets/levels/{}.json", id);
+        let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let level: LevelData = serde_json::from_str(&text).map_err(|e| format!("{}: {}", id, e))?;
+        let mut textures = HashMap::new();
+        for name in level.textures.iter().filter(|name| !self.textures.contains_key(*name)) {
+            textures.insert(name.to_string(), headless_texture(name)?);
+        }
+        let mut data = HashMap::new();
+        for puzzle in &level.puzzles {
+            let path = format!("assets/levels/{}/{}.json", id, puzzle.tag);
+            data.insert(puzzle.tag.clone(), std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?);
+        }
+        self.swap_level(id, level, textures, data);
+        Ok(())
+    }
+
+    fn swap_level(
+        &mut self,
+        id: &str,
+        level: LevelData,
+        textures: HashMap<String, Texture2D>,
+        data: HashMap<String, String>,
+    ) {
+        self.unload_level(&level);
+        self.textures.extend(textures);
+        self.data.extend(data);
+        self.level = Some((id.to_string(), level));
+    }
+
+    // A copy with edits to the level's files and any textures in use picked
+    // up, for hot reloading in dev builds. It only replaces these once the
+    // game has been rebuilt on it. Replaced textures aren't freed since the
+    // game being reloaded may still draw with them
+
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    pub async fn reloaded(&self, changed: &[std::path::PathBuf]) -> Result<Assets, String> {
+        let mut assets = self.clone();
+        assets.reload(changed).await?;
+        Ok(assets)
+    }
+
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    async fn reload(&mut self, changed: &[std::path::PathBuf]) -> Result<(), String> {
+        for path in changed {
+            if path.extension().is_some_and(|e| e == "png") {
+                let Some(name) = path.file_stem().and_then(|n| n.to_str()) else { continue };
+                if self.textures.contains_key(name) {
+                    let texture = load_texture(&format!("assets/{}.png", name)).await.map_err(|e| e.to_string())?;
+                    self.textures.insert(name.to_string(), texture);
+                }
+            }
+        }
+
+        let id = self.level_id().to_string();
+        let text = load_string(&format!("assets/levels/{}.json", id)).await.map_err(|e| e.to_string())?;
+        let level: LevelData = serde_json::from_str(&text).map_err(|e| format!("{}: {}", id, e))?;
+        for name in &level.textures {
+            if !self.textures.contains_key(name) {
+                let texture = load_texture(&format!("assets/{}.png", name)).await.map_err(|e| e.to_string())?;
+                self.textures.insert(name.to_string(), texture);
+            }
+        }
+        for puzzle in &level.puzzles {
+            let path = format!("assets/levels/{}/{}.json", id, puzzle.tag);
+            let text = load_string(&path).await.map_err(|e| e.to_string())?;
+            self.data.insert(puzzle.tag.clone(), text);
+        }
+        self.level = Some((id, level));
+        Ok(())
+    }
+
+    fn unload_level(&mut self, next: &LevelData) {
+        let Some((_, level)) = self.level.take() else { return };
+        for name in &level.textures {
+            if next.textures.contains(name) {
+                continue;
+            }
+            if let Some(texture) = self.textures.remove(name) {
+                if !self.headless {
+                    texture.delete();
+                }
+            }
+        }
+        for puzzle in &level.puzzles {
+            self.data.remove(&puzzle.tag);
+        }
+    }
+
+    pub fn level(&self) -> &LevelData {
+        match &self.level {
+            Some((_, level)) => level,
+            None => panic!("No level has been loaded"),
+        }
+    }
+    pub fn level_id(&self) -> &str {
+        match &self.level {
+            Some((id, _)) => id,
+            None => panic!("No level has been loaded"),
+        }
+    }
+    // Textures are looked up while a level is built, so one the level
+    // forgot to list is a load error
+    pub fn texture(&self, name: &str) -> Result<Texture2D, String> {
+        match self.textures.get(name) {
+            Some(texture) => Ok(*texture),
+            None => Err(format!("Texture {} was never loaded", name)),
+        }
+    }
+    pub fn data(&self, name: &str) -> &str {
+        match self.data.get(name) {
+            Some(text) => text,
+            None => panic!("Data file {} was never loaded", name),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use macroquad::audio::{load_sound, play_sound, PlaySoundParams, Sound};
+use std::collections::HashMap;
+
+// Sound effects, by file name in assets/ without the .wav
+
+const SOUNDS: &[&str] = &["Wrong", "Right", "Ring"];
+
+pub struct Audio {
+    sounds: HashMap<String, Sound>,
+}
+
+impl Audio {
+    pub async fn load() -> Self {
+        let mut sounds = HashMap::new();
+        for name in SOUNDS {
+            match load_sound(&format!("assets/{}.wav", name)).await {
+                Ok(sound) => {
+                    sounds.insert(name.to_string(), sound);
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        Audio { sounds }
+    }
+    pub fn play(&self, name: &str, volume: f32) {
+        if volume <= 0f32 {
+            return;
+        }
+        if let Some(sound) = self.sounds.get(name) {
+            play_sound(*sound, PlaySoundParams { looped: false, volume });
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::input::FrameInput;
+use crate::net;
+use crate::ui;
+use macroquad::prelude::*;
+
+// Seconds a line stays up with the chat closed
+
+const LINE_TIME: f32 = 10.0;
+
+// Lines shown at once, newest at the bottom
+
+const SHOWN: usize = 6;
+
+// Long enough for a code and where it was found
+
+pub const MAX_LENGTH: usize = 80;
+
+struct Line {
+    from: usize,
+    text: String,
+    age: f32,
+}
+
+// Text chat between co-op players, along the bottom left over the game
+
+#[derive(Default)]
+pub struct Chat {
+    lines: Vec<Line>,
+    // What's being typed, while the chat's open
+    typing: Option<String>,
+}
+
+impl Chat {
+    pub fn new() -> Self {
+        Chat::default()
+    }
+
+    pub fn open(&self) -> bool {
+        self.typing.is_some()
+    }
+
+    // Opens when told to, then takes the keyboard until Enter sends what was
+    // typed or Escape throws it away. Hands back anything sent
+    pub fn update(&mut self, input: &FrameInput, start: bool) -> Option<String> {
+        for line in &mut self.lines {
+            line.age += input.dt;
+        }
+        let Some(typing) = &mut self.typing else {
+            if start {
+                self.typing = Some(String::new());
+            }
+            return None;
+        };
+        if input.key(KeyCode::Escape) {
+            self.typing = None;
+            return None;
+        }
+        if input.key(KeyCode::Backspace) {
+            typing.pop();
+        }
+        for c in &input.chars {
+            if !c.is_control() && typing.chars().count() < MAX_LENGTH {
+                typing.push(*c);
+            }
+        }
+        if input.key(KeyCode::Enter) {
+            let text = self.typing.take().unwrap();
+            let text = text.trim();
+            return (!text.is_empty()).then(|| text.to_string());
+        }
+        None
+    }
+
+    // What another player sent, cut down to what could have been typed here
+    pub fn clean(text: &str) -> Option<String> {
+        let text: String = text.chars().filter(|c| !c.is_control()).take(MAX_LENGTH).collect();
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    pub fn add(&mut self, from: usize, text: &str) {
+        self.lines.push(Line { from, text: text.to_string(), age: 0f32 });
+    }
+
+    pub fn draw(&self) {
+        let open = self.open();
+        let lines: Vec<&Line> = self.lines.iter().filter(|line| open || line.age < LINE_TIME).collect();
+        let lines = &lines[lines.len().saturating_sub(SHOWN)..];
+        let bottom = 530.0;
+        if open || !lines.is_empty() {
+            let height = (lines.len() + open as usize) as f32 * 24.0 + 10.0;
+            draw_rectangle(10.0, bottom - height + 18.0, 500.0, height, Color::new(0f32, 0f32, 0f32, 0.6));
+        }
+        for (i, line) in lines.iter().rev().enumerate() {
+            let y = bottom - (i + open as usize) as f32 * 24.0;
+            let name = format!("P{}:", line.from + 1);
+            draw_text(&name, 20.0, y, 24.0, net::color(line.from));
+            draw_text(&line.text, 60.0, y, 24.0, WHITE);
+        }
+        match &self.typing {
+            Some(typing) => draw_text(&format!("> {}_", typing), 20.0, bottom, 24.0, YELLOW),
+            None => {
+                draw_rectangle(390.0, 572.0, 150.0, 24.0, Color::new(0f32, 0f32, 0f32, 0.6));
+                ui::text("[Enter] Chat", 400.0, 590.0, 24.0, WHITE);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str, keys: &[KeyCode]) -> FrameInput {
+        let mut input = FrameInput::empty(0);
+        input.chars = text.chars().collect();
+        input.keys = keys.to_vec();
+        input
+    }
+
+    #[test]
+    fn enter_sends_what_was_typed() {
+        let mut chat = Chat::new();
+        assert_eq!(chat.update(&typed("", &[]), true), None);
+        assert!(chat.open());
+        chat.update(&typed(" code is 12", &[]), false);
+        chat.update(&typed("", &[KeyCode::Backspace]), false);
+        assert_eq!(chat.update(&typed("34 ", &[KeyCode::Enter]), false), Some("code is 134".to_string()));
+        assert!(!chat.open());
+    }
+
+    #[test]
+    fn escape_throws_it_away() {
+        let mut chat = Chat::new();
+        chat.update(&typed("", &[]), true);
+        chat.update(&typed("never mind", &[]), false);
+        assert_eq!(chat.update(&typed("", &[KeyCode::Escape]), false), None);
+        assert!(!chat.open());
+    }
+
+    #[test]
+    fn typing_stops_at_the_limit() {
+        let mut chat = Chat::new();
+        chat.update(&typed("", &[]), true);
+        let sent = chat.update(&typed(&"a".repeat(MAX_LENGTH + 5), &[KeyCode::Enter]), false);
+        assert_eq!(sent.map(|text| text.len()), Some(MAX_LENGTH));
+    }
+
+    #[test]
+    fn sent_text_gets_cleaned() {
+        assert_eq!(Chat::clean(&"b".repeat(MAX_LENGTH * 2)).map(|text| text.len()), Some(MAX_LENGTH));
+        assert_eq!(Chat::clean(" hi\nthere\u{7} "), Some("hithere".to_string()));
+        assert_eq!(Chat::clean("\n\t "), None);
+    }
+}
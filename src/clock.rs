@@ -0,0 +1,242 @@
+use crate::input::FrameInput;
+use crate::touch;
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::f32::consts::TAU;
+
+// How close to a hand a press has to be to pick it up
+
+const GRAB_DISTANCE: f32 = 20.0;
+
+#[derive(Deserialize, Clone, Debug)]
+struct ClockData {
+    center: [f32; 2],
+    hour_length: f32,
+    minute_length: f32,
+    // Hours and minutes, on a twelve hour face
+    start: [u32; 2],
+    #[serde(default)]
+    answer: Option<[u32; 2]>,
+    // Minutes the minute hand snaps to
+    #[serde(default = "default_step")]
+    step: u32,
+    // Runs on its own from the start time as the game goes on, with hands
+    // that can't be moved
+    #[serde(default)]
+    live: bool,
+    #[serde(default)]
+    solved_text: Option<String>,
+}
+
+fn default_step() -> u32 {
+    5
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Hand {
+    Hour,
+    Minute,
+}
+
+// A clock face whose hands get dragged round to a target time, checked
+// every time a hand is let go. Live clocks just tell the time instead
+
+#[derive(Clone)]
+pub struct ClockPuzzle {
+    data: ClockData,
+    // Minutes past twelve
+    pub time: u32,
+    held: Option<Hand>,
+    elapsed: f32,
+}
+
+// Angle of a point around the center, clockwise from twelve
+
+fn angle(center: Vec2, at: Vec2) -> f32 {
+    let d = at - center;
+    d.x.atan2(-d.y).rem_euclid(TAU)
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = ((p - a).dot(ab) / ab.length_squared()).clamp(0f32, 1f32);
+    p.distance(a + ab * t)
+}
+
+impl ClockPuzzle {
+    pub fn new(data: &str) -> Result<Self, String> {
+        let data: ClockData = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        Ok(ClockPuzzle {
+            time: (data.start[0] % 12) * 60 + data.start[1],
+            data,
+            held: None,
+            elapsed: 0f32,
+        })
+    }
+
+    fn center(&self) -> Vec2 {
+        vec2(self.data.center[0], self.data.center[1])
+    }
+
+    // Live clocks move on a minute for every second of play
+    fn shown(&self) -> u32 {
+        match self.data.live {
+            true => (self.time + self.elapsed as u32) % 720,
+            false => self.time,
+        }
+    }
+
+    fn tip(&self, hand: Hand) -> Vec2 {
+        let time = self.shown() as f32;
+        let (turns, length) = match hand {
+            Hand::Hour => (time / 720f32, self.data.hour_length),
+            Hand::Minute => ((time % 60f32) / 60f32, self.data.minute_length),
+        };
+        let a = turns * TAU;
+        self.center() + vec2(a.sin(), -a.cos()) * length
+    }
+
+    // The minute hand is on top, so it wins when both are in reach
+    fn hand_at(&self, at: Vec2, touch: bool) -> Option<Hand> {
+        let reach = GRAB_DISTANCE + touch::reach(touch);
+        [Hand::Minute, Hand::Hour]
+            .into_iter()
+            .find(|hand| distance_to_segment(at, self.center(), self.tip(*hand)) < reach)
+    }
+
+    fn drag(&mut self, hand: Hand, at: Vec2) {
+        let turns = angle(self.center(), at) / TAU;
+        let (hours, minutes) = (self.time / 60, self.time % 60);
+        match hand {
+            Hand::Hour => self.time = ((turns * 12f32) as u32 % 12) * 60 + minutes,
+            Hand::Minute => {
+                let step = self.data.step.max(1);
+                let minutes = ((turns * 60f32 / step as f32).round() as u32 * step) % 60;
+                self.time = hours * 60 + minutes;
+            }
+        }
+    }
+
+    // A saved time, in minutes past twelve
+    pub fn restore(&mut self, time: u32) -> Result<(), String> {
+        if time >= 720 {
+            return Err(format!("{} minutes is past the twelve hours on the face", time));
+        }
+        self.time = time;
+        self.held = None;
+        Ok(())
+    }
+
+    // Game time so far, which live clocks show
+    pub fn tick(&mut self, elapsed: f32) {
+        self.elapsed = elapsed;
+    }
+
+    // Returns whether a hand was let go on the right time
+    pub fn update(&mut self, input: &FrameInput) -> bool {
+        if self.data.live {
+            return false;
+        }
+        let mouse = vec2(input.mouse.x, input.mouse.y);
+        if input.click().is_some() {
+            self.held = self.hand_at(mouse, input.touch);
+        }
+        if let Some(hand) = self.held {
+            self.drag(hand, mouse);
+        }
+        if input.released.contains(&MouseButton::Left) && self.held.take().is_some() {
+            return self.solved();
+        }
+        false
+    }
+
+    pub fn solved(&self) -> bool {
+        self.data.answer.is_some_and(|[h, m]| (h % 12) * 60 + m == self.time)
+    }
+
+    pub fn solved_text(&self) -> Option<String> {
+        self.data.solved_text.clone()
+    }
+
+    pub fn cancel(&mut self) {
+        self.held = None;
+    }
+
+    pub fn draw(&self) {
+        let center = self.center();
+        for (hand, thickness) in [(Hand::Hour, 10f32), (Hand::Minute, 6f32)] {
+            let tip = self.tip(hand);
+            let color = match self.held == Some(hand) {
+                true => DARKGRAY,
+                false => BLACK,
+            };
+            draw_line(center.x, center.y, tip.x, tip.y, thickness, color);
+        }
+        draw_circle(center.x, center.y, 9f32, BLACK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pos;
+
+    fn clock() -> ClockPuzzle {
+        ClockPuzzle::new(&std::fs::read_to_string("assets/levels/room/big_clock.json").unwrap()).unwrap()
+    }
+
+    fn input(x: f32, y: f32, pressed: bool) -> FrameInput {
+        let mut input = FrameInput::empty(0);
+        input.mouse = Pos::new(x, y);
+        match pressed {
+            true => input.pressed.push(MouseButton::Left),
+            false => input.released.push(MouseButton::Left),
+        }
+        input
+    }
+
+    #[test]
+    fn hands_drag_round_to_the_answer() {
+        let mut clock = clock();
+        assert_eq!(clock.time, 70);
+
+        // Minute hand from ten past round to a quarter past
+        clock.update(&input(382f32, 199f32, true));
+        assert!(!clock.update(&input(432f32, 228f32, false)));
+        assert_eq!(clock.time, 75);
+
+        // Then the hour hand on to three
+        clock.update(&input(355f32, 198f32, true));
+        assert!(clock.update(&input(400f32, 228f32, false)));
+        assert_eq!(clock.time, 195);
+    }
+
+    #[test]
+    fn presses_away_from_the_hands_do_nothing() {
+        let mut clock = clock();
+        clock.update(&input(250f32, 300f32, true));
+        assert!(!clock.update(&input(432f32, 228f32, false)));
+        assert_eq!(clock.time, 70);
+    }
+
+    #[test]
+    fn live_clocks_keep_time_and_ignore_drags() {
+        let mut clock = ClockPuzzle::new(
+            r#"{ "center": [100, 100], "hour_length": 40, "minute_length": 60, "start": [11, 50], "live": true }"#,
+        )
+        .unwrap();
+        clock.tick(25f32);
+        assert_eq!(clock.shown(), 15);
+        clock.update(&input(100f32, 60f32, true));
+        assert!(!clock.update(&input(160f32, 100f32, false)));
+        assert_eq!(clock.time, 710);
+    }
+
+    #[test]
+    fn restore_stays_on_the_face() {
+        let mut clock = clock();
+        assert!(clock.restore(720).is_err());
+        clock.restore(195).unwrap();
+        assert!(clock.solved());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::flags::{Condition, Flags};
+use crate::storage;
+use crate::ui::{self, tr};
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+// Seconds before the next line of an ending fades in
+
+const LINE_DELAY: f32 = 1.2;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Ending {
+    pub id: String,
+    pub title: String,
+    pub trigger: Condition,
+    pub lines: Vec<String>,
+    #[serde(default)]
+    pub closing: String,
+    // The game stops here rather than letting the player carry on
+    #[serde(default)]
+    pub last: bool,
+}
+
+impl Ending {
+    // How long until everything is on screen
+    pub fn length(&self) -> f32 {
+        LINE_DELAY * (self.lines.len() + 1) as f32
+    }
+    pub fn finished(&self, time: f32) -> bool {
+        time >= self.length()
+    }
+
+    // Lines come in one at a time, the closing text after all of them
+
+    pub fn draw(&self, time: f32) {
+        for (i, line) in self.lines.iter().enumerate() {
+            let alpha = ((time - LINE_DELAY * i as f32) / LINE_DELAY).clamp(0f32, 1f32);
+            let color = Color::new(1f32, 1f32, 1f32, alpha);
+            ui::text(line, 20.0, 100.0 + (i as f32 * 40.0), 38.0, color);
+        }
+        if self.finished(time) {
+            let closing = tr(&self.closing);
+            let size = measure_text(&closing, None, 80, 1f32);
+            draw_text(&closing, 400.0 - size.width / 2.0, 420.0, 80.0, YELLOW);
+            if !self.last {
+                ui::text("Click to keep playing", 20.0, 570.0, 30.0, GRAY);
+            }
+        }
+    }
+}
+
+// Every ending in the game, along with which ones have ever been reached
+
+pub struct Endings {
+    list: Vec<Ending>,
+    unlocked: Vec<String>,
+}
+
+impl Endings {
+    pub fn new(data: &str) -> Self {
+        let list: Vec<Ending> = serde_json::from_str(data).unwrap();
+        let unlocked = storage::load("endings")
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Endings { list, unlocked }
+    }
+    pub fn get(&self, index: usize) -> &Ending {
+        &self.list[index]
+    }
+    // First ending whose trigger is met, skipping ones already seen this run
+    pub fn triggered(&self, flags: &Flags, reached: &[String]) -> Option<usize> {
+        self.list
+            .iter()
+            .position(|ending| !reached.contains(&ending.id) && ending.trigger.met(flags))
+    }
+    pub fn unlock(&mut self, id: &str) {
+        if self.unlocked.iter().any(|u| u == id) {
+            return;
+        }
+        self.unlocked.push(id.to_string());
+        if let Ok(text) = serde_json::to_string(&self.unlocked) {
+            storage::save("endings", &text);
+        }
+    }
+
+    pub fn draw_gallery(&self) {
+        ui::text("Endings", 20.0, 60.0, 60.0, YELLOW);
+        let found = self.list.iter().filter(|e| self.unlocked.contains(&e.id)).count();
+        let progress = tr("{} of {} found")
+            .replacen("{}", &found.to_string(), 1)
+            .replacen("{}", &self.list.len().to_string(), 1);
+        ui::text(&progress, 20.0, 100.0, 30.0, GRAY);
+        for (i, ending) in self.list.iter().enumerate() {
+            let y = 170.0 + (i as f32 * 80.0);
+            if self.unlocked.contains(&ending.id) {
+                ui::text(&ending.title, 40.0, y, 40.0, WHITE);
+                let hint = ending.lines.iter().find(|line| !line.is_empty()).cloned().unwrap_or_default();
+                ui::text(&hint, 40.0, y + 30.0, 24.0, GRAY);
+            } else {
+                draw_text("???", 40.0, y, 40.0, DARKGRAY);
+            }
+        }
+        ui::text("Click to go back", 20.0, 570.0, 30.0, GRAY);
+    }
+}
@@ -0,0 +1,66 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+
+// Story progress is tracked as a set of named flags, things like
+// "solved:door_pad" or "clicked:open_door", which data files can test against
+
+pub type Flags = HashSet<String>;
+
+#[derive(Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct Condition {
+    #[serde(default)]
+    pub all: Vec<String>,
+    #[serde(default)]
+    pub any: Vec<String>,
+    #[serde(default)]
+    pub none: Vec<String>,
+}
+
+impl Condition {
+    pub fn met(&self, flags: &Flags) -> bool {
+        self.all.iter().all(|flag| flags.contains(flag))
+            && (self.any.is_empty() || self.any.iter().any(|flag| flags.contains(flag)))
+            && !self.none.iter().any(|flag| flags.contains(flag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(names: &[&str]) -> Flags {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn condition(all: &[&str], any: &[&str], none: &[&str]) -> Condition {
+        let list = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        Condition { all: list(all), any: list(any), none: list(none) }
+    }
+
+    #[test]
+    fn empty_condition_always_holds() {
+        assert!(Condition::default().met(&flags(&[])));
+        assert!(Condition::default().met(&flags(&["light:off"])));
+    }
+
+    #[test]
+    fn all_needs_every_flag() {
+        let c = condition(&["solved:door_pad", "light:off"], &[], &[]);
+        assert!(c.met(&flags(&["solved:door_pad", "light:off", "clicked:vase"])));
+        assert!(!c.met(&flags(&["solved:door_pad"])));
+    }
+
+    #[test]
+    fn any_needs_one_flag() {
+        let c = condition(&[], &["called:pumpkin", "called:cactus"], &[]);
+        assert!(c.met(&flags(&["called:cactus"])));
+        assert!(!c.met(&flags(&["called:raspberry"])));
+    }
+
+    #[test]
+    fn none_rules_a_flag_out() {
+        let c = condition(&["solved:door_pad"], &[], &["light:off"]);
+        assert!(c.met(&flags(&["solved:door_pad"])));
+        assert!(!c.met(&flags(&["solved:door_pad", "light:off"])));
+    }
+}
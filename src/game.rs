@@ -0,0 +1,998 @@
+use crate::achievements::Achievements;
+use crate::animation::Animator;
+use crate::assets::Assets;
+use crate::endings::Endings;
+use crate::flags::{Condition, Flags};
+use crate::input::FrameInput;
+use crate::journal::{Clue, Entry, Journal};
+use crate::notes::{Notes, ScratchPad};
+use crate::puzzle::{Outcome, Puzzle};
+use crate::script::{Action, Scripts};
+use crate::telemetry::{Event, EventKind};
+use crate::ui;
+use crate::level::{Side, View, ViewExit};
+use crate::{Bounds, Item, ItemState, Pos, UserState};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// Where the button that gets the notes out sits, in every view
+
+const NOTES_BUTTON: Rect = Rect { x: 650.0, y: 565.0, w: 150.0, h: 35.0 };
+
+// Everything that makes up a running game, updated from input each frame and
+// drawn separately so it can also run without a window
+
+pub struct Game {
+    level: String,
+    main_text: Vec<String>,
+    items: Vec<Item>,
+    views: Vec<View>,
+    darkness: Option<Condition>,
+    current_view: String,
+    current_state: UserState,
+    current_item: Option<Item>,
+    // Detail views further back, the back arrow returns to these one at a time
+    parents: Vec<Item>,
+    // Puzzles by the tag of the detail view they're in
+    puzzles: HashMap<String, Puzzle>,
+    // Hints by puzzle tag, for the puzzles that have one
+    hints: HashMap<String, String>,
+    scripts: Scripts,
+    // Animated items by tag, and the flags their triggers have already seen
+    animators: HashMap<String, Animator>,
+    seen_flags: Flags,
+    // Clues found so far, open over everything else when shown
+    journal: Journal,
+    journal_open: bool,
+    // The player's own scribbles, drawn over any room or detail view
+    notes: ScratchPad,
+    notes_open: bool,
+    left_arrow: Texture2D,
+    elapsed: f32,
+    puzzle_time: f32,
+    puzzle_totals: HashMap<String, f32>,
+    events: Vec<Event>,
+    flags: Flags,
+    endings: Endings,
+    achievements: Achievements,
+    ending: Option<usize>,
+    ending_time: f32,
+    endings_reached: Vec<String>,
+    // When the last ending was reached, for best times
+    finish_time: f32,
+    sounds: Vec<String>,
+    colorblind: bool,
+    // Which items this player gets to see, when roles are split in co-op
+    role: Side,
+    // Endings and achievements reached count for the player, which they
+    // don't in the host's copy of someone else's game
+    keep_records: bool,
+}
+
+impl Game {
+    pub fn new(assets: &Assets) -> Result<Self, String> {
+        let level = assets.level();
+
+        let mut puzzles = HashMap::new();
+        for puzzle in &level.puzzles {
+            let state = Puzzle::new(puzzle.kind, assets.data(&puzzle.tag), assets)
+                .map_err(|e| format!("Couldn't load puzzle {}: {}", puzzle.tag, e))?;
+            puzzles.insert(puzzle.tag.clone(), state);
+        }
+
+        let mut game = Game {
+            level: assets.level_id().to_string(),
+            main_text: Vec::new(),
+            items: level.items(assets)?,
+            views: level.views(assets)?,
+            darkness: level.darkness.clone(),
+            current_view: level.start.clone(),
+            current_state: UserState::Nothing,
+            current_item: None,
+            parents: Vec::new(),
+            puzzles,
+            hints: level.puzzles.iter().filter_map(|p| Some((p.tag.clone(), p.hint.clone()?))).collect(),
+            scripts: level.scripts()?,
+            animators: level.animators(assets)?,
+            seen_flags: Flags::new(),
+            journal: Journal::new(),
+            journal_open: false,
+            notes: ScratchPad::new(),
+            notes_open: false,
+            left_arrow: assets.texture("ArrowLeft")?,
+            elapsed: 0f32,
+            puzzle_time: 0f32,
+            puzzle_totals: HashMap::new(),
+            events: Vec::new(),
+            flags: Flags::new(),
+            endings: Endings::new(assets.data("endings")),
+            achievements: Achievements::new(assets.data("achievements")),
+            ending: None,
+            ending_time: 0f32,
+            endings_reached: Vec::new(),
+            finish_time: 0f32,
+            sounds: Vec::new(),
+            colorblind: false,
+            role: Side::Everyone,
+            keep_records: true,
+        };
+        game.log(EventKind::LevelStart { level: game.level.clone() });
+        Ok(game)
+    }
+
+    // The same game on freshly loaded level data, for hot reloading in dev
+    // builds. Progress comes across through the save, and whatever detail
+    // views were open get found again by tag
+
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    pub fn reloaded(&self, assets: &Assets) -> Result<Game, String> {
+        let mut game = Game::new(assets)?;
+        game.events.clear();
+        game.restore(&self.save_data())?;
+        game.colorblind = self.colorblind;
+        game.role = self.role;
+        game.keep_records = self.keep_records;
+        game.puzzle_time = self.puzzle_time;
+        game.finish_time = self.finish_time;
+        game.journal_open = self.journal_open;
+        game.notes_open = self.notes_open;
+
+        let find = |item: &Item| game.items.iter().find(|i| i.tag == item.tag && i.view == item.view).cloned();
+        let stack: Option<Vec<Item>> = self.parents.iter().chain(&self.current_item).map(find).collect();
+        match (&self.current_state, stack) {
+            (UserState::Looking | UserState::Interacting, Some(mut stack)) => {
+                game.current_item = stack.pop();
+                game.parents = stack;
+                game.current_state = match self.current_state {
+                    UserState::Looking => UserState::Looking,
+                    _ => UserState::Interacting,
+                };
+            }
+            (UserState::Complete, _) => {
+                game.current_state = UserState::Complete;
+                game.ending = self.ending;
+                game.ending_time = self.ending_time;
+            }
+            _ => (),
+        }
+        Ok(game)
+    }
+
+    pub fn update(&mut self, input: &FrameInput) {
+        let mouse = input.click();
+
+        self.elapsed += input.dt;
+        for animator in self.animators.values_mut() {
+            animator.update(input.dt);
+        }
+        for puzzle in self.puzzles.values_mut() {
+            puzzle.tick(self.elapsed);
+        }
+        self.achievements.update(input.dt);
+        if self.current_state == UserState::Interacting {
+            self.puzzle_time += input.dt;
+        }
+
+        // Notes come out with Tab or the button in the corner, and take all
+        // the input while they're out so typing doesn't set anything off
+
+        let notes_button = mouse.is_some_and(|m| NOTES_BUTTON.contains(vec2(m.x, m.y)));
+        let playing = !matches!(self.current_state, UserState::Complete | UserState::Gallery);
+        if playing && (input.key(KeyCode::Tab) || notes_button) {
+            self.notes_open = !self.notes_open;
+            self.notes.cancel();
+            return;
+        }
+        if self.notes_open {
+            self.notes.update(input);
+            return;
+        }
+
+        // The journal opens from anywhere, and holds everything else up
+        // while it's open
+
+        if input.key(KeyCode::J) && self.current_state != UserState::Complete {
+            self.journal_open = !self.journal_open;
+            return;
+        }
+        if self.journal_open {
+            self.journal.scroll(input.wheel);
+            if mouse.is_some() {
+                self.journal_open = false;
+            }
+            return;
+        }
+
+        if let Some(m) = mouse {
+            let tag = self.item_at(m).map(|item| item.tag.clone());
+            self.log(EventKind::Click { view: self.view_name(), x: m.x, y: m.y, tag });
+        }
+
+        // Handle default state of looking around the room
+
+        if self.current_state == UserState::Nothing {
+
+            // Main items loop, for clicking
+
+            if input.key(KeyCode::G) {
+                self.current_state = UserState::Gallery;
+                return;
+            }
+
+            if let Some(m) = mouse {
+                self.click_items(m);
+
+                // Arrows and hotspots leading to other views
+
+                let exit = self.exits().find(|exit| exit.area.contains(vec2(m.x, m.y)));
+                if let Some(to) = exit.map(|exit| exit.to.clone()) {
+                    self.take_exit(to);
+                }
+            }
+
+            // Swiping on a touchscreen goes the way of the arrow it's set on
+
+            if let Some(swipe) = input.swipe {
+                let exit = self.exits().find(|exit| exit.swipe == Some(swipe));
+                if let Some(to) = exit.map(|exit| exit.to.clone()) {
+                    self.take_exit(to);
+                }
+            }
+        }
+
+        // Handle state of currently looking at an item, anything placed in
+        // the view can be clicked, and back goes up one view at a time
+
+        else if self.current_state == UserState::Looking {
+            if let Some(m) = mouse {
+                if m.x > 0.0 && m.x < 100.0 && m.y > 20.0 && m.y < 120.0 {
+                    self.back();
+                } else {
+                    self.click_items(m);
+                }
+            }
+        }
+
+        // Handle state of interacting with object, going to be specific to item
+
+        else if self.current_state == UserState::Interacting {
+            let item = self.linked_item();
+
+            if input.key(KeyCode::H) {
+                self.hint(&item.tag);
+            }
+            self.update_puzzle(&item.tag, input);
+
+            // Give UI to go back
+
+            if let Some(m) = mouse {
+                if self.current_state == UserState::Interacting {
+                    if m.x > 0.0 && m.x < 100.0 && m.y > 20.0 && m.y < 120.0 {
+                        self.leave_interaction();
+                    } else {
+                        self.click_items(m);
+                    }
+                }
+            }
+        }
+
+        // Handle an ending playing out, clicking hurries it along
+
+        else if self.current_state == UserState::Complete {
+            let ending = self.endings.get(self.ending.unwrap());
+            self.ending_time += input.dt;
+            if mouse.is_some() {
+                if !ending.finished(self.ending_time) {
+                    self.ending_time = ending.length();
+                } else if !ending.last {
+                    self.current_state = UserState::Nothing;
+                    self.ending = None;
+                }
+            }
+        }
+
+        else if self.current_state == UserState::Gallery
+            && (mouse.is_some() || input.key(KeyCode::G)) {
+            self.current_state = UserState::Nothing;
+        }
+
+        self.check_endings();
+        if self.keep_records {
+            self.achievements.check(&self.level, &self.flags, self.elapsed);
+        }
+        self.trigger_animations();
+    }
+
+    // Clips start the moment their flag gets set, however it was set
+
+    fn trigger_animations(&mut self) {
+        if self.flags == self.seen_flags {
+            return;
+        }
+        for animator in self.animators.values_mut() {
+            let clips: Vec<String> = animator
+                .triggers
+                .iter()
+                .filter(|t| self.flags.contains(&t.flag) && !self.seen_flags.contains(&t.flag))
+                .map(|t| t.play.clone())
+                .collect();
+            for clip in clips {
+                animator.play(&clip);
+            }
+        }
+        self.seen_flags = self.flags.clone();
+    }
+
+    fn check_endings(&mut self) {
+        if self.current_state == UserState::Complete {
+            return;
+        }
+        let Some(index) = self.endings.triggered(&self.flags, &self.endings_reached) else { return };
+        let id = self.endings.get(index).id.clone();
+        if self.current_state == UserState::Interacting {
+            self.leave_interaction();
+        }
+        if self.keep_records {
+            self.endings.unlock(&id);
+        }
+        self.flags.insert(format!("ending:{}", id));
+        self.endings_reached.push(id.clone());
+        self.log(EventKind::Ending { id });
+        self.current_state = UserState::Complete;
+        self.current_item = None;
+        self.parents.clear();
+        self.ending = Some(index);
+        self.ending_time = 0f32;
+        self.finish_time = self.elapsed;
+    }
+
+    fn log(&mut self, kind: EventKind) {
+        self.events.push(Event { t: self.elapsed, kind });
+    }
+
+    // Hands over everything logged since last time, for the telemetry file
+
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn set_colorblind(&mut self, colorblind: bool) {
+        self.colorblind = colorblind;
+    }
+
+    // Which side's items this player sees when co-op roles are split
+    pub fn set_role(&mut self, role: Side) {
+        self.role = role;
+    }
+
+    pub fn set_keep_records(&mut self, keep_records: bool) {
+        self.keep_records = keep_records;
+    }
+
+    // Whether the keyboard's being typed into, by the notes or a puzzle
+    pub fn typing(&self) -> bool {
+        let puzzle = || self.puzzles.get(&self.linked_item().tag).is_some_and(|puzzle| puzzle.typing());
+        self.notes_open || (self.current_state == UserState::Interacting && puzzle())
+    }
+
+    // Sound effects asked for since last time, by name
+
+    pub fn take_sounds(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.sounds)
+    }
+
+    // Reached an ending there's no carrying on from
+
+    pub fn finished(&self) -> bool {
+        self.ending.is_some_and(|index| self.endings.get(index).last)
+    }
+
+    // The last ending has played all the way through, so the level is done
+    // and it's time to move on
+
+    pub fn ending_shown(&self) -> bool {
+        self.finished() && self.endings.get(self.ending.unwrap()).finished(self.ending_time)
+    }
+
+    pub fn level(&self) -> &str {
+        &self.level
+    }
+
+    pub fn finish_time(&self) -> f32 {
+        self.finish_time
+    }
+
+    // Topmost item in the current room under a point
+
+    fn item_at(&self, point: Pos) -> Option<&Item> {
+        self.items
+            .iter()
+            .rev()
+            .find(|item| self.present(item) && item.contains(point))
+    }
+
+    // In the view on screen, be it a room or a detail view, on this player's
+    // side, and not waiting on something to happen first
+
+    fn present(&self, item: &Item) -> bool {
+        item.view.as_ref() == Some(&self.view_name())
+            && (self.role == Side::Everyone || item.side == Side::Everyone || item.side == self.role)
+            && item.requires.met(&self.flags)
+            && !self.flags.contains(&format!("hidden:{}", item.tag))
+    }
+
+    // Clicks go to every item under the mouse, the last one listed wins
+
+    fn click_items(&mut self, m: Pos) {
+        let clicked: Vec<Item> = self.items.iter().filter(|i| self.present(i) && i.contains(m)).cloned().collect();
+        let mut opened = None;
+        let mut scripts = Vec::new();
+        for item in clicked {
+            self.flags.insert(format!("clicked:{}", item.tag));
+            if let Some(flag) = &item.toggle {
+                if !self.flags.remove(flag) {
+                    self.flags.insert(flag.clone());
+                }
+            }
+            scripts.push(format!("click:{}", item.tag));
+            match item.state {
+                ItemState::Flavor => {
+                    if item.journal {
+                        for line in item.flavor_text.iter().filter(|line| !line.is_empty()) {
+                            self.journal.record(&self.current_view, Clue::Text(line.clone()));
+                        }
+                    }
+                    self.main_text = item.flavor_text.clone();
+                    opened = None;
+                }
+                ItemState::Look | ItemState::Interact => opened = Some(item),
+                ItemState::Nothing => (),
+            }
+        }
+        if let Some(item) = opened {
+            self.open(item);
+        }
+        for script in scripts {
+            self.run_script(&script);
+        }
+    }
+
+    // Scripts only see copies of the game, what they asked for is applied
+    // here once they're done
+
+    fn run_script(&mut self, name: &str) {
+        if !self.scripts.has(name) {
+            return;
+        }
+        let puzzles = self.puzzles.iter().map(|(tag, puzzle)| (tag.clone(), puzzle.save())).collect();
+        for action in self.scripts.run(name, &self.flags, puzzles, &self.current_view) {
+            match action {
+                Action::SetFlag(flag) => {
+                    self.flags.insert(flag);
+                }
+                Action::ClearFlag(flag) => {
+                    self.flags.remove(&flag);
+                }
+                Action::Say(lines) => self.main_text = lines,
+                Action::Go(view) => self.go(view),
+                Action::Play(sound) => self.sounds.push(sound),
+                Action::Animate(tag, clip) => match self.animators.get_mut(&tag) {
+                    Some(animator) => animator.play(&clip),
+                    None => eprintln!("Script {} animated {}, which has no animations", name, tag),
+                },
+                Action::SetPuzzle(tag, saved) => {
+                    let Some(puzzle) = self.puzzles.get_mut(&tag) else { continue };
+                    if let Err(e) = puzzle.restore(saved) {
+                        eprintln!("Script {} broke puzzle {}: {}", name, tag, e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn take_exit(&mut self, to: String) {
+        self.current_view = to;
+        self.main_text = vec!["".to_string()];
+        self.log(EventKind::RoomChange { room: self.current_view.clone() });
+    }
+
+    // Straight to another view, out of any detail views on the way
+
+    fn go(&mut self, view: String) {
+        if !self.views.iter().any(|v| v.name == view) {
+            eprintln!("There's no view {} to go to", view);
+            return;
+        }
+        if self.current_state == UserState::Interacting {
+            self.leave_puzzle();
+        }
+        self.current_state = UserState::Nothing;
+        self.current_item = None;
+        self.parents.clear();
+        self.current_view = view;
+        self.log(EventKind::RoomChange { room: self.current_view.clone() });
+    }
+
+    // Goes into an item's detail view, on top of whichever one is open
+
+    fn open(&mut self, item: Item) {
+        if self.current_state == UserState::Interacting {
+            self.leave_puzzle();
+        }
+        if let Some(parent) = self.current_item.take() {
+            self.parents.push(parent);
+        }
+        self.enter(item);
+    }
+
+    fn enter(&mut self, item: Item) {
+        self.current_state = match item.state {
+            ItemState::Interact => UserState::Interacting,
+            _ => UserState::Looking,
+        };
+        self.current_item = Some(item);
+        self.main_text = vec!["".to_string()];
+        if self.current_state == UserState::Looking {
+            self.journal.record(&self.current_view, Clue::Picture(self.linked_item().tag));
+        }
+        if self.current_state == UserState::Interacting {
+            self.puzzle_time = 0f32;
+            self.log(EventKind::PuzzleEnter { puzzle: self.linked_item().tag });
+        }
+    }
+
+    // One view back, out to the room once there's nothing left
+
+    fn back(&mut self) {
+        match self.parents.pop() {
+            Some(parent) => self.enter(parent),
+            None => {
+                self.current_state = UserState::Nothing;
+                self.current_item = None;
+            }
+        }
+    }
+
+    fn dark(&self) -> bool {
+        self.darkness.as_ref().is_some_and(|darkness| darkness.met(&self.flags))
+    }
+
+    fn view(&self) -> Option<&View> {
+        self.views.iter().find(|view| view.name == self.current_view)
+    }
+
+    // Ways out of the current view that are open right now
+    fn exits(&self) -> impl Iterator<Item = &ViewExit> {
+        self.view()
+            .into_iter()
+            .flat_map(|view| &view.exits)
+            .filter(|exit| exit.requires.met(&self.flags))
+    }
+
+    // Name of what's on screen, a room or the tag of the item being looked at
+
+    pub fn view_name(&self) -> String {
+        match self.current_state {
+            UserState::Nothing => self.current_view.clone(),
+            UserState::Looking | UserState::Interacting => self.linked_item().tag,
+            UserState::Complete => "ending".to_string(),
+            UserState::Gallery => "gallery".to_string(),
+        }
+    }
+
+    // Label of the room the player's in, even while looking at something in it
+
+    pub fn room_label(&self) -> String {
+        self.view().map(|view| view.label.clone()).unwrap_or_default()
+    }
+
+    fn leave_interaction(&mut self) {
+        self.leave_puzzle();
+        self.back();
+    }
+
+    // Stops the clock on the puzzle on screen, whether it's being left or
+    // something inside it is being opened
+    fn leave_puzzle(&mut self) {
+        let puzzle = self.linked_item().tag;
+        if let Some(state) = self.puzzles.get_mut(&puzzle) {
+            state.cancel();
+        }
+        *self.puzzle_totals.entry(puzzle.clone()).or_insert(0f32) += self.puzzle_time;
+        self.log(EventKind::PuzzleLeave { puzzle, seconds: self.puzzle_time });
+        self.puzzle_time = 0f32;
+    }
+
+    // Solve time counts every visit to the puzzle, not just the last one
+
+    fn puzzle_solved(&mut self) {
+        let puzzle = self.linked_item().tag;
+        let seconds = self.puzzle_totals.get(&puzzle).unwrap_or(&0f32) + self.puzzle_time;
+        self.flags.insert(format!("solved:{}", puzzle));
+        self.sounds.push("Right".to_string());
+        self.log(EventKind::PuzzleSolved { puzzle, seconds });
+    }
+
+    fn wrong_attempt(&mut self, entry: String) {
+        self.sounds.push("Wrong".to_string());
+        let puzzle = self.linked_item().tag;
+        self.flags.insert(format!("wrong:{}", puzzle));
+        self.log(EventKind::WrongAttempt { puzzle, entry });
+    }
+
+    // The texture being looked at or interacted with, should be some guarantees
+
+    fn linked_item(&self) -> Item {
+        *self.current_item.clone().unwrap().link.unwrap()
+    }
+
+    // Any hint at all sets hinted, for achievements that go without
+    fn hint(&mut self, tag: &str) {
+        let Some(hint) = self.hints.get(tag) else { return };
+        self.main_text = vec![hint.clone()];
+        self.flags.insert(format!("hint:{}", tag));
+        self.flags.insert("hinted".to_string());
+    }
+
+    fn update_puzzle(&mut self, tag: &str, input: &FrameInput) {
+        let Some(puzzle) = self.puzzles.get_mut(tag) else { return };
+        for outcome in puzzle.update(input) {
+            match outcome {
+                Outcome::Solved(text) => {
+                    self.puzzle_solved();
+                    self.leave_interaction();
+                    if let Some(text) = text {
+                        self.main_text = vec![text];
+                    }
+                    self.run_script(&format!("solve:{}", tag));
+                }
+                Outcome::Wrong(entry) => self.wrong_attempt(entry),
+                Outcome::Flag(flag) => {
+                    self.flags.insert(flag);
+                }
+                Outcome::Sound(sound) => self.sounds.push(sound),
+                Outcome::Clue(clue) => self.journal.record(&self.current_view, clue),
+            }
+        }
+    }
+
+    pub fn draw(&self) {
+
+        // Background by view, detail views stay white
+
+        let bg = match (&self.current_state, self.view()) {
+            (UserState::Nothing, Some(view)) => view.color,
+            _ => WHITE,
+        };
+        clear_background(bg);
+
+        if self.current_state == UserState::Nothing {
+            for item in &self.items {
+                if self.present(item) && !item.glow {
+                    self.draw_item(item);
+                }
+            }
+
+            // With the lights out only glowing things show through, the
+            // arrows and text stay on top so there's always a way around
+
+            if self.dark() {
+                draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.02, 0.02, 0.06, 0.85));
+            }
+            for item in &self.items {
+                if self.present(item) && item.glow {
+                    self.draw_item(item);
+                }
+            }
+
+            // Arrows to other views, hotspots have nothing to draw
+
+            for exit in self.exits() {
+                if let Some(texture) = exit.texture {
+                    draw_texture(texture, exit.area.x, exit.area.y, WHITE);
+                }
+            }
+
+            // Draw any global text (flavor text from items)
+
+            for (i, text) in self.main_text.iter().enumerate() {
+                ui::text(text, 20.0, 25.0 + ((i as f32) * 25.0 * ui::text_scale()), 30.0, WHITE);
+            }
+
+            // Show which view in top right
+
+            if let Some(view) = self.view() {
+                draw_text(&view.label, 605.0, 40.0, 50.0, RED);
+            }
+
+            ui::text("[G] Endings", 20.0, 590.0, 24.0, WHITE);
+            ui::text("[J] Journal", 200.0, 590.0, 24.0, WHITE);
+        }
+
+        else if self.current_state == UserState::Looking
+            || self.current_state == UserState::Interacting {
+
+            // Show linked item
+
+            let item = self.linked_item();
+            self.draw_item(&item);
+
+            // Anything placed in the view sits on top of it
+
+            for item in &self.items {
+                if self.present(item) {
+                    self.draw_item(item);
+                }
+            }
+
+            if self.current_state == UserState::Interacting {
+                self.draw_interaction(&item);
+                if self.hints.contains_key(&item.tag) {
+                    ui::text("[H] Hint", NOTES_BUTTON.x + 10.0, NOTES_BUTTON.y - 10.0, 24.0, BLACK);
+                }
+            }
+
+            // Flavor text from anything in here goes along the bottom
+
+            for (i, text) in self.main_text.iter().enumerate() {
+                ui::text(text, 20.0, 540.0 + ((i as f32) * 25.0 * ui::text_scale()), 30.0, BLACK);
+            }
+
+            // Give UI go back button
+
+            draw_texture(self.left_arrow, 0.0, 20.0, WHITE);
+        }
+
+        // Handle the game being finished
+
+        else if self.current_state == UserState::Complete {
+            clear_background(BLACK);
+            self.endings.get(self.ending.unwrap()).draw(self.ending_time);
+        }
+
+        else if self.current_state == UserState::Gallery {
+            clear_background(BLACK);
+            self.endings.draw_gallery();
+        }
+
+        if matches!(self.current_state, UserState::Nothing | UserState::Looking | UserState::Interacting) {
+            let color = if self.current_state == UserState::Nothing { WHITE } else { BLACK };
+            ui::text("[Tab] Notes", NOTES_BUTTON.x + 10.0, NOTES_BUTTON.y + 25.0, 24.0, color);
+        }
+
+        if self.journal_open {
+            let rooms: Vec<(String, String)> =
+                self.views.iter().map(|view| (view.name.clone(), view.label.clone())).collect();
+            self.journal.draw(&rooms, |tag| self.items.iter().find(|item| item.tag == tag).map(|item| item.texture));
+        }
+        if self.notes_open {
+            self.notes.draw();
+        }
+        self.achievements.draw_toast();
+    }
+
+    fn draw_item(&self, item: &Item) {
+        let at = vec2(item.position.x, item.position.y);
+        match self.animators.get(&item.tag) {
+            Some(animator) => animator.draw(item.texture, at),
+            None => draw_texture(item.texture, at.x, at.y, WHITE),
+        }
+    }
+
+    // Handle specific states by item
+
+    fn draw_interaction(&self, item: &Item) {
+        if let Some(puzzle) = self.puzzles.get(&item.tag) {
+            puzzle.draw(self.colorblind);
+        }
+    }
+
+    // Progress that survives quitting, items a solve reveals come back on
+    // their own from the flags
+
+    pub fn save_data(&self) -> String {
+        let data = SaveData {
+            level: self.level.clone(),
+            room: self.current_view.clone(),
+            main_text: self.main_text.clone(),
+            flags: self.flags.iter().cloned().collect(),
+            puzzles: self.puzzles.iter().map(|(tag, puzzle)| (tag.clone(), puzzle.save())).collect(),
+            elapsed: self.elapsed,
+            puzzle_totals: self.puzzle_totals.clone(),
+            endings_reached: self.endings_reached.clone(),
+            journal: self.journal.entries.clone(),
+            notes: self.notes.notes.clone(),
+        };
+        serde_json::to_string(&data).unwrap()
+    }
+
+    pub fn restore(&mut self, text: &str) -> Result<(), String> {
+        let data: SaveData = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if data.level != self.level {
+            return Err(format!("save is for level {}, not {}", data.level, self.level));
+        }
+        if !self.views.iter().any(|view| view.name == data.room) {
+            return Err(format!("save is in view {}, which isn't in the level", data.room));
+        }
+        // Nothing changes until every puzzle's taken its saved state
+        self.puzzles = self.restored_puzzles(data.puzzles)?;
+        self.current_view = data.room;
+        self.main_text = data.main_text;
+        self.flags = data.flags.into_iter().collect();
+        self.seen_flags = self.flags.clone();
+        for animator in self.animators.values_mut() {
+            let done: Vec<String> =
+                animator.triggers.iter().filter(|t| self.flags.contains(&t.flag)).map(|t| t.play.clone()).collect();
+            for clip in done {
+                animator.finish(&clip);
+            }
+        }
+        self.elapsed = data.elapsed;
+        self.puzzle_totals = data.puzzle_totals;
+        self.endings_reached = data.endings_reached;
+        self.journal.entries = data.journal;
+        self.notes.notes = data.notes;
+        Ok(())
+    }
+
+    // Saved puzzle states restored into copies of the puzzles, so one that
+    // doesn't fit leaves every puzzle as it was
+
+    fn restored_puzzles(
+        &self,
+        saved: impl IntoIterator<Item = (String, serde_json::Value)>,
+    ) -> Result<HashMap<String, Puzzle>, String> {
+        let mut puzzles = self.puzzles.clone();
+        for (tag, saved) in saved {
+            if let Some(puzzle) = puzzles.get_mut(&tag) {
+                puzzle.restore(saved).map_err(|e| format!("{}: {}", tag, e))?;
+            }
+        }
+        Ok(puzzles)
+    }
+
+    // The part of the game everyone in a co-op game shares, flags and puzzles.
+    // Sorted so the same state always comes out as the same text
+
+    pub fn shared_state(&self) -> String {
+        let mut flags: Vec<String> = self.flags.iter().cloned().collect();
+        flags.sort();
+        let puzzles = self.puzzles.iter().map(|(tag, puzzle)| (tag.clone(), puzzle.save())).collect();
+        serde_json::to_string(&SharedState { flags, puzzles }).unwrap()
+    }
+
+    // Items and animations catch up with new flags on their own next update.
+    // Flags and puzzles change together or not at all
+    pub fn apply_shared_state(&mut self, text: &str) -> Result<(), String> {
+        let data: SharedState = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        self.puzzles = self.restored_puzzles(data.puzzles)?;
+        self.flags = data.flags.into_iter().collect();
+        Ok(())
+    }
+
+    // Every room in the level, in the order the level file lists them
+
+    pub fn view_names(&self) -> Vec<String> {
+        self.views.iter().map(|view| view.name.clone()).collect()
+    }
+
+    // Where everything sits in a room or detail view, as tag and bounds. A
+    // detail view is its own texture with whatever's placed in it on top
+
+    pub fn view_layout(&self, view: &str) -> Vec<(String, Bounds)> {
+        let detail = self.items.iter().filter(|item| item.tag == view).take(1);
+        let placed = self.items.iter().filter(|item| item.view.as_deref() == Some(view));
+        detail.chain(placed).map(|item| (item.tag.clone(), item.bounds())).collect()
+    }
+
+    // One line description of where things stand, for checking replays
+
+    pub fn summary(&self) -> String {
+        let item = match &self.current_item {
+            Some(item) => item.tag.as_str(),
+            None => "none",
+        };
+        let ending = match self.ending {
+            Some(index) => self.endings.get(index).id.as_str(),
+            None => "none",
+        };
+        format!(
+            "state={:?} room={} item={} ending={}",
+            self.current_state, self.current_view, item, ending
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveData {
+    level: String,
+    room: String,
+    main_text: Vec<String>,
+    flags: Vec<String>,
+    puzzles: HashMap<String, serde_json::Value>,
+    elapsed: f32,
+    puzzle_totals: HashMap<String, f32>,
+    endings_reached: Vec<String>,
+    #[serde(default)]
+    journal: Vec<Entry>,
+    #[serde(default)]
+    notes: Notes,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SharedState {
+    flags: Vec<String>,
+    puzzles: BTreeMap<String, serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room() -> Game {
+        let mut assets = Assets::load_headless();
+        assets.load_level_headless("room").unwrap();
+        Game::new(&assets).unwrap()
+    }
+
+    #[test]
+    fn missing_level_keeps_the_last_one() {
+        let mut assets = Assets::load_headless();
+        assets.load_level_headless("room").unwrap();
+        assert!(assets.load_level_headless("nowhere").is_err());
+        assert_eq!(assets.level_id(), "room");
+        assert!(Game::new(&assets).is_ok());
+    }
+
+    #[test]
+    fn bad_save_leaves_the_game_alone() {
+        let mut game = room();
+        let fresh: serde_json::Value = serde_json::from_str(&game.save_data()).unwrap();
+        let mut data = fresh.clone();
+        data["room"] = "West".into();
+        data["flags"] = serde_json::json!(["solved:door_pad"]);
+        data["puzzles"]["door_pad"] = serde_json::json!([0, 0, 0, 0]);
+        data["puzzles"]["big_clock"] = serde_json::json!(9999);
+        assert!(game.restore(&data.to_string()).is_err());
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&game.save_data()).unwrap(), fresh);
+    }
+
+    #[test]
+    fn good_save_comes_back() {
+        let mut game = room();
+        let mut data: serde_json::Value = serde_json::from_str(&game.save_data()).unwrap();
+        data["room"] = "West".into();
+        data["puzzles"]["big_clock"] = serde_json::json!(195);
+        game.restore(&data.to_string()).unwrap();
+        assert_eq!(game.view_name(), "West");
+        assert_eq!(game.puzzles["big_clock"].save(), serde_json::json!(195));
+    }
+
+    #[test]
+    fn hints_are_flagged() {
+        let mut game = room();
+        game.hint("phone_entry");
+        assert!(!game.flags.contains("hinted"));
+
+        game.hint("door_pad");
+        assert_eq!(game.main_text, vec![game.hints["door_pad"].clone()]);
+        assert!(game.flags.contains("hint:door_pad") && game.flags.contains("hinted"));
+    }
+
+    #[test]
+    fn bad_shared_state_leaves_the_game_alone() {
+        let mut game = room();
+        let before = game.shared_state();
+        let mut data: serde_json::Value = serde_json::from_str(&before).unwrap();
+        data["flags"] = serde_json::json!(["solved:door_pad", "light:off"]);
+        data["puzzles"]["candlecase_big"] = serde_json::json!([0, 0, 0, 0]);
+        assert!(game.apply_shared_state(&data.to_string()).is_err());
+        assert_eq!(game.shared_state(), before);
+
+        data["puzzles"]["candlecase_big"] = serde_json::json!([0, 1, 2, 3]);
+        game.apply_shared_state(&data.to_string()).unwrap();
+        assert!(game.flags.contains("light:off"));
+        assert_eq!(game.puzzles["candlecase_big"].save(), serde_json::json!([0, 1, 2, 3]));
+    }
+}
@@ -0,0 +1,326 @@
+use crate::touch::{Gestures, Swipe};
+use crate::Pos;
+use macroquad::prelude::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+
+const HEADER: &str = "# escape room input recording v1";
+const SAVE_PREFIX: &str = "# save ";
+const LEVEL_PREFIX: &str = "# level ";
+
+// Keys we care about recording, typed text goes through chars instead
+
+const TRACKED_KEYS: &[KeyCode] = &[
+    KeyCode::Escape, KeyCode::Enter, KeyCode::Backspace, KeyCode::Tab, KeyCode::Space,
+    KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+    KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+    KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+    KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4,
+];
+
+const TRACKED_BUTTONS: &[MouseButton] = &[MouseButton::Left, MouseButton::Right];
+
+// Everything the game reads from the player in a single frame
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameInput {
+    pub frame: u64,
+    pub dt: f32,
+    pub mouse: Pos,
+    pub pressed: Vec<MouseButton>,
+    pub released: Vec<MouseButton>,
+    pub down: Vec<MouseButton>,
+    pub wheel: f32,
+    pub keys: Vec<KeyCode>,
+    pub chars: Vec<char>,
+    // Coming from a touchscreen, so small things are easier to hit
+    pub touch: bool,
+    pub swipe: Option<Swipe>,
+}
+
+impl FrameInput {
+    pub fn empty(frame: u64) -> Self {
+        FrameInput {
+            frame,
+            dt: 0f32,
+            mouse: Pos::new(0f32, 0f32),
+            pressed: Vec::new(),
+            released: Vec::new(),
+            down: Vec::new(),
+            wheel: 0f32,
+            keys: Vec::new(),
+            chars: Vec::new(),
+            touch: false,
+            swipe: None,
+        }
+    }
+    fn live(frame: u64, gestures: &mut Gestures) -> Self {
+        let mut input = FrameInput::empty(frame);
+        input.dt = get_frame_time();
+        input.mouse = Pos::tuple(mouse_position());
+        for button in TRACKED_BUTTONS {
+            if is_mouse_button_pressed(*button) {
+                input.pressed.push(*button);
+            }
+            if is_mouse_button_released(*button) {
+                input.released.push(*button);
+            }
+            if is_mouse_button_down(*button) {
+                input.down.push(*button);
+            }
+        }
+        input.wheel = mouse_wheel().1;
+        for key in TRACKED_KEYS {
+            if is_key_pressed(*key) {
+                input.keys.push(*key);
+            }
+        }
+        while let Some(c) = get_char_pressed() {
+            input.chars.push(c);
+        }
+        gestures.update(&mut input);
+        input.touch = gestures.active;
+        input
+    }
+    // Position of a left click this frame, which is what most of the game wants
+    pub fn click(&self) -> Option<Pos> {
+        if self.pressed.contains(&MouseButton::Left) {
+            Some(self.mouse)
+        } else {
+            None
+        }
+    }
+    pub fn key(&self, key: KeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+    pub fn to_line(&self) -> String {
+        let mut line = format!("{} {} {} {}", self.frame, self.dt, self.mouse.x, self.mouse.y);
+        for button in &self.pressed {
+            line += &format!(" press:{:?}", button);
+        }
+        for button in &self.released {
+            line += &format!(" release:{:?}", button);
+        }
+        if self.wheel != 0f32 {
+            line += &format!(" wheel:{}", self.wheel);
+        }
+        for key in &self.keys {
+            line += &format!(" key:{:?}", key);
+        }
+        for c in &self.chars {
+            line += &format!(" char:{}", *c as u32);
+        }
+        if self.touch {
+            line += " touch:1";
+        }
+        if let Some(swipe) = self.swipe {
+            line += &format!(" swipe:{:?}", swipe);
+        }
+        line
+    }
+    // Buttons held down aren't stored per line, replay tracks them from press/release
+    pub fn from_line(line: &str, held: &mut Vec<MouseButton>) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let mut input = FrameInput::empty(parts.next()?.parse().ok()?);
+        input.dt = parts.next()?.parse().ok()?;
+        input.mouse = Pos::new(parts.next()?.parse().ok()?, parts.next()?.parse().ok()?);
+        for part in parts {
+            let (kind, value) = part.split_once(':')?;
+            match kind {
+                "press" => input.pressed.push(parse_button(value)?),
+                "release" => input.released.push(parse_button(value)?),
+                "wheel" => input.wheel = value.parse().ok()?,
+                "key" => input.keys.push(parse_key(value)?),
+                "char" => input.chars.push(char::from_u32(value.parse().ok()?)?),
+                "touch" => input.touch = value == "1",
+                "swipe" => input.swipe = Some(parse_swipe(value)?),
+                _ => return None,
+            }
+        }
+        for button in &input.pressed {
+            if !held.contains(button) {
+                held.push(*button);
+            }
+        }
+        // A tap presses and lets go in the same frame
+        held.retain(|b| !input.released.contains(b));
+        input.down = held.clone();
+        Some(input)
+    }
+}
+
+fn parse_button(name: &str) -> Option<MouseButton> {
+    TRACKED_BUTTONS.iter().find(|b| format!("{:?}", b) == name).copied()
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    TRACKED_KEYS.iter().find(|k| format!("{:?}", k) == name).copied()
+}
+
+fn parse_swipe(name: &str) -> Option<Swipe> {
+    [Swipe::Left, Swipe::Right].into_iter().find(|s| format!("{:?}", s) == name)
+}
+
+// A recorded session: the level it was played in, the save it started from
+// if it was continued, and every frame of input fed to the game from then on
+
+pub struct Recording {
+    pub level: Option<String>,
+    pub save: Option<String>,
+    pub frames: Vec<FrameInput>,
+}
+
+// Reads a whole recording up front, so a bad file fails before anything runs
+
+pub fn load_recording(path: &str) -> Result<Recording, String> {
+    let file = File::open(path).map_err(|e| format!("Couldn't open {}: {}", path, e))?;
+    let mut recording = Recording { level: None, save: None, frames: Vec::new() };
+    let mut held = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Couldn't read {}: {}", path, e))?;
+        if let Some(save) = line.strip_prefix(SAVE_PREFIX) {
+            recording.save = Some(save.to_string());
+            continue;
+        }
+        if let Some(level) = line.strip_prefix(LEVEL_PREFIX) {
+            recording.level = Some(level.to_string());
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let input = FrameInput::from_line(&line, &mut held)
+            .ok_or(format!("{}:{}: malformed input line", path, i + 1))?;
+        recording.frames.push(input);
+    }
+    Ok(recording)
+}
+
+pub struct Recorder {
+    writer: LineWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &str, level: &str, save: Option<&str>) -> Option<Self> {
+        if let Some(dir) = std::path::Path::new(path).parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        match File::create(path) {
+            Ok(file) => {
+                let mut writer = LineWriter::new(file);
+                let _ = writeln!(writer, "{}", HEADER);
+                let _ = writeln!(writer, "{}{}", LEVEL_PREFIX, level);
+                if let Some(save) = save {
+                    let _ = writeln!(writer, "{}{}", SAVE_PREFIX, save);
+                }
+                Some(Recorder { writer })
+            }
+            Err(e) => {
+                eprintln!("Couldn't record input to {}: {}", path, e);
+                None
+            }
+        }
+    }
+    pub fn write(&mut self, input: &FrameInput) {
+        let _ = writeln!(self.writer, "{}", input.to_line());
+    }
+}
+
+// Where the main loop gets its input from each frame: the player, or a
+// recording being played back
+
+pub struct InputSource {
+    frame: u64,
+    replay: std::vec::IntoIter<FrameInput>,
+    gestures: Gestures,
+}
+
+impl InputSource {
+    pub fn live() -> Self {
+        InputSource::replay(Vec::new())
+    }
+    pub fn replay(frames: Vec<FrameInput>) -> Self {
+        InputSource {
+            frame: 0,
+            replay: frames.into_iter(),
+            gestures: Gestures::default(),
+        }
+    }
+    pub fn replaying(&self) -> bool {
+        self.replay.len() > 0
+    }
+    // Once a replay runs out, control goes back to the player
+    pub fn next(&mut self) -> FrameInput {
+        let input = match self.replay.next() {
+            Some(input) => input,
+            None => FrameInput::live(self.frame, &mut self.gestures),
+        };
+        self.frame = input.frame + 1;
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_round_trip() {
+        let mut input = FrameInput::empty(12);
+        input.dt = 0.016;
+        input.mouse = Pos::new(310.5, 42.0);
+        input.pressed.push(MouseButton::Left);
+        input.down.push(MouseButton::Left);
+        input.wheel = -1.0;
+        input.keys = vec![KeyCode::Enter, KeyCode::J];
+        input.chars = vec!['1', ' ', 'é'];
+        input.touch = true;
+        input.swipe = Some(Swipe::Left);
+
+        let line = input.to_line();
+        assert_eq!(
+            line,
+            "12 0.016 310.5 42 press:Left wheel:-1 key:Enter key:J char:49 char:32 char:233 touch:1 swipe:Left"
+        );
+        assert_eq!(FrameInput::from_line(&line, &mut Vec::new()), Some(input));
+    }
+
+    #[test]
+    fn held_buttons_carry_over_until_released() {
+        let mut held = Vec::new();
+        let lines = ["0 0.1 5 5 press:Left", "1 0.1 6 5 press:Right", "2 0.1 7 5 release:Left", "3 0.1 7 5"];
+        let down: Vec<Vec<MouseButton>> =
+            lines.iter().map(|line| FrameInput::from_line(line, &mut held).unwrap().down).collect();
+        assert_eq!(
+            down,
+            vec![
+                vec![MouseButton::Left],
+                vec![MouseButton::Left, MouseButton::Right],
+                vec![MouseButton::Right],
+                vec![MouseButton::Right],
+            ]
+        );
+    }
+
+    #[test]
+    fn taps_are_never_held() {
+        let mut held = Vec::new();
+        let input = FrameInput::from_line("0 0.1 5 5 press:Left release:Left touch:1", &mut held).unwrap();
+        assert!(input.click().is_some());
+        assert!(input.down.is_empty());
+        assert!(held.is_empty());
+    }
+
+    #[test]
+    fn bad_lines_are_rejected() {
+        let mut held = Vec::new();
+        assert_eq!(FrameInput::from_line("0 0.1 5", &mut held), None);
+        assert_eq!(FrameInput::from_line("0 0.1 5 5 press:Middle", &mut held), None);
+        assert_eq!(FrameInput::from_line("0 0.1 5 5 swipe:Up", &mut held), None);
+        assert_eq!(FrameInput::from_line("0 0.1 5 5 jump:1", &mut held), None);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::ui;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+
+// Size thumbnails get fit into
+
+const THUMBNAIL: f32 = 90.0;
+
+// Room left under the last entry for the hint to close it
+
+const BOTTOM_MARGIN: f32 = 60.0;
+
+// Something worth writing down, kept in English and translated when shown
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Clue {
+    Text(String),
+    // A number that picked up, and what was said
+    Call { number: String, response: String },
+    // Tag of a detail view, drawn small
+    Picture(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub room: String,
+    pub clue: Clue,
+}
+
+// Clues written down as they turn up, so nobody needs paper. Shown over
+// whatever's going on, grouped by the room they were found in
+
+#[derive(Default)]
+pub struct Journal {
+    pub entries: Vec<Entry>,
+    scroll: f32,
+    // How far the pages ran off the bottom of the screen when last drawn
+    overflow: Cell<f32>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Journal::default()
+    }
+
+    // Anything already written down isn't written again
+    pub fn record(&mut self, room: &str, clue: Clue) {
+        let entry = Entry { room: room.to_string(), clue };
+        if !self.entries.contains(&entry) {
+            self.entries.push(entry);
+        }
+    }
+
+    // Stops once the last entry is on screen
+    pub fn scroll(&mut self, wheel: f32) {
+        self.scroll = (self.scroll + wheel * 30.0).clamp(-self.overflow.get(), 0f32);
+    }
+
+    // Rooms come in the order given, as name and label, and pictures are
+    // looked up by tag
+    pub fn draw(&self, rooms: &[(String, String)], picture: impl Fn(&str) -> Option<Texture2D>) {
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.1, 0.08, 0.05, 0.95));
+        let mut y = 60.0 + self.scroll;
+        ui::text("Journal", 20.0, y, 60.0, YELLOW);
+        y += 30.0;
+        if self.entries.is_empty() {
+            ui::text("Nothing written down yet", 40.0, y + 40.0, 30.0, GRAY);
+        }
+
+        for (room, label) in rooms {
+            let entries: Vec<&Clue> = self.entries.iter().filter(|e| &e.room == room).map(|e| &e.clue).collect();
+            if entries.is_empty() {
+                continue;
+            }
+            y += 50.0;
+            ui::text(label, 20.0, y, 40.0, ORANGE);
+
+            // Pictures side by side, then everything said underneath
+
+            let mut x = 40.0;
+            let pictures: Vec<Texture2D> = entries
+                .iter()
+                .filter_map(|clue| match clue {
+                    Clue::Picture(tag) => picture(tag),
+                    _ => None,
+                })
+                .collect();
+            if !pictures.is_empty() {
+                y += 15.0;
+                for texture in pictures {
+                    if x + THUMBNAIL > screen_width() {
+                        x = 40.0;
+                        y += THUMBNAIL + 10.0;
+                    }
+                    let scale = THUMBNAIL / texture.width().max(texture.height());
+                    let size = vec2(texture.width(), texture.height()) * scale;
+                    let params = DrawTextureParams { dest_size: Some(size), ..Default::default() };
+                    draw_texture_ex(texture, x, y, WHITE, params);
+                    x += THUMBNAIL + 10.0;
+                }
+                y += THUMBNAIL;
+            }
+            for clue in entries {
+                let line = match clue {
+                    Clue::Text(text) => ui::tr(text),
+                    Clue::Call { number, response } => format!("{}: {}", number, ui::tr(response)),
+                    Clue::Picture(_) => continue,
+                };
+                y += 35.0;
+                draw_text(&line, 40.0, y, 30.0 * ui::text_scale(), WHITE);
+            }
+        }
+        let height = y - self.scroll + BOTTOM_MARGIN;
+        self.overflow.set((height - screen_height()).max(0f32));
+
+        ui::text("[J] Close", 20.0, 590.0, 24.0, GRAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clues_are_written_down_once_per_room() {
+        let mut journal = Journal::new();
+        journal.record("North", Clue::Text("1776".to_string()));
+        journal.record("North", Clue::Picture("north_open_book".to_string()));
+        journal.record("North", Clue::Text("1776".to_string()));
+        journal.record("East", Clue::Text("1776".to_string()));
+        let rooms: Vec<&str> = journal.entries.iter().map(|e| e.room.as_str()).collect();
+        assert_eq!(rooms, vec!["North", "North", "East"]);
+    }
+
+    #[test]
+    fn scrolling_stays_on_the_pages() {
+        let mut journal = Journal::new();
+        journal.scroll(-1f32);
+        assert_eq!(journal.scroll, 0f32);
+
+        journal.overflow.set(100f32);
+        journal.scroll(-2f32);
+        assert_eq!(journal.scroll, -60f32);
+        journal.scroll(-5f32);
+        assert_eq!(journal.scroll, -100f32);
+        journal.scroll(10f32);
+        assert_eq!(journal.scroll, 0f32);
+    }
+}
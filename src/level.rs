@@ -0,0 +1,278 @@
+use crate::animation::{Animator, ClipData, Trigger};
+use crate::assets::Assets;
+use crate::flags::Condition;
+use crate::puzzle::PuzzleKind;
+use crate::script::Scripts;
+use crate::storage;
+use crate::touch::Swipe;
+use crate::{Item, ItemState, Pos};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// One entry in assets/campaign.json, levels are played in the listed order
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LevelInfo {
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PuzzleData {
+    // Tag of the detail view the puzzle lives in, its data is
+    // assets/levels/<level>/<tag>.json
+    pub tag: String,
+    pub kind: PuzzleKind,
+    // Script run once the puzzle is solved
+    #[serde(default)]
+    pub on_solve: Option<String>,
+    // Said when the player asks for help with H, which sets hint:<tag>
+    #[serde(default)]
+    pub hint: Option<String>,
+}
+
+// A way from one view to another, clicked anywhere in its area. Arrows
+// get a texture drawn at the area's corner, hidden hotspots leave it out
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Exit {
+    pub to: String,
+    pub area: [f32; 4],
+    #[serde(default)]
+    pub texture: Option<String>,
+    #[serde(default)]
+    pub requires: Condition,
+    // Swiping this way on a touchscreen takes the exit too
+    #[serde(default)]
+    pub swipe: Option<Swipe>,
+}
+
+// Somewhere the player can stand and look around, like a wall, the ceiling
+// or under a table
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ViewData {
+    pub name: String,
+    // Shown in the corner so the player knows where they are
+    pub label: String,
+    pub color: [f32; 3],
+    pub exits: Vec<Exit>,
+}
+
+// A view ready to play, with its exits' textures looked up
+
+pub struct View {
+    pub name: String,
+    pub label: String,
+    pub color: Color,
+    pub exits: Vec<ViewExit>,
+}
+
+pub struct ViewExit {
+    pub to: String,
+    pub area: Rect,
+    pub texture: Option<Texture2D>,
+    pub requires: Condition,
+    pub swipe: Option<Swipe>,
+}
+
+// Which player gets to see an item when the clues and puzzles are split
+// between two players
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Clues,
+    Puzzles,
+    #[default]
+    Everyone,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ItemData {
+    pub tag: String,
+    // Detail views that only show up through a link are left without a view
+    #[serde(default)]
+    pub view: Option<String>,
+    pub texture: String,
+    pub at: [f32; 2],
+    #[serde(default)]
+    pub state: ItemState,
+    #[serde(default)]
+    pub flavor: Vec<String>,
+    #[serde(default)]
+    pub link: Option<String>,
+    // Item only exists while this holds, like an open door after a solve
+    #[serde(default)]
+    pub requires: Condition,
+    // Script run whenever the item is clicked, after whatever it does anyway
+    #[serde(default)]
+    pub on_click: Option<String>,
+    // Story flag that clicking flips on and off, like a light switch
+    #[serde(default)]
+    pub toggle: Option<String>,
+    // Drawn over the darkness, like glow in the dark writing
+    #[serde(default)]
+    pub glow: bool,
+    // Flavor text that's a clue, and goes in the journal when read
+    #[serde(default)]
+    pub journal: bool,
+    // Who sees it with split roles, left out it goes by the item's state
+    #[serde(default)]
+    pub side: Option<Side>,
+    // Named animation clips, the one in animation plays from the start
+    #[serde(default)]
+    pub animations: HashMap<String, ClipData>,
+    #[serde(default)]
+    pub animation: Option<String>,
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+}
+
+// Everything in a level, from assets/levels/<id>.json
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LevelData {
+    pub start: String,
+    pub views: Vec<ViewData>,
+    pub textures: Vec<String>,
+    #[serde(default)]
+    pub puzzles: Vec<PuzzleData>,
+    pub items: Vec<ItemData>,
+    // Every room goes dark while this holds, never if left out
+    #[serde(default)]
+    pub darkness: Option<Condition>,
+}
+
+impl LevelData {
+    pub fn scripts(&self) -> Result<Scripts, String> {
+        let mut scripts = Scripts::new();
+        for item in &self.items {
+            if let Some(source) = &item.on_click {
+                scripts.add(&format!("click:{}", item.tag), source)?;
+            }
+        }
+        for puzzle in &self.puzzles {
+            if let Some(source) = &puzzle.on_solve {
+                scripts.add(&format!("solve:{}", puzzle.tag), source)?;
+            }
+        }
+        Ok(scripts)
+    }
+
+    pub fn view(&self, name: &str) -> Option<&ViewData> {
+        self.views.iter().find(|view| view.name == name)
+    }
+
+    // Every exit has to lead to a view that's in the level
+    pub fn views(&self, assets: &Assets) -> Result<Vec<View>, String> {
+        self.views.iter().map(|view| self.built_view(view, assets)).collect()
+    }
+
+    fn built_view(&self, view: &ViewData, assets: &Assets) -> Result<View, String> {
+        let mut exits = Vec::new();
+        for exit in &view.exits {
+            if self.view(&exit.to).is_none() {
+                return Err(format!("{} has an exit to {}, which isn't in the level", view.name, exit.to));
+            }
+            let [x, y, w, h] = exit.area;
+            exits.push(ViewExit {
+                to: exit.to.clone(),
+                area: Rect::new(x, y, w, h),
+                texture: exit.texture.as_ref().map(|name| assets.texture(name)).transpose()?,
+                requires: exit.requires.clone(),
+                swipe: exit.swipe,
+            });
+        }
+        Ok(View {
+            name: view.name.clone(),
+            label: view.label.clone(),
+            color: Color::new(view.color[0], view.color[1], view.color[2], 1f32),
+            exits,
+        })
+    }
+
+    // Animated items by tag
+    pub fn animators(&self, assets: &Assets) -> Result<HashMap<String, Animator>, String> {
+        let mut animators = HashMap::new();
+        for item in self.items.iter().filter(|item| !item.animations.is_empty()) {
+            let mut animator = Animator::new(&item.animations, &item.triggers, assets)?;
+            if let Some(clip) = &item.animation {
+                animator.play(clip);
+            }
+            animators.insert(item.tag.clone(), animator);
+        }
+        Ok(animators)
+    }
+
+    // Links are by tag, built out into the linked item's own copy
+    pub fn items(&self, assets: &Assets) -> Result<Vec<Item>, String> {
+        self.items.iter().map(|data| self.item(data, assets)).collect()
+    }
+
+    fn item(&self, data: &ItemData, assets: &Assets) -> Result<Item, String> {
+        let link = match &data.link {
+            Some(tag) => match self.items.iter().find(|i| i.tag == *tag) {
+                Some(linked) => Some(Box::new(self.item(linked, assets)?)),
+                None => return Err(format!("{} links to {}, which isn't in the level", data.tag, tag)),
+            },
+            None => None,
+        };
+        let mut item = Item::new(
+            data.view.clone(),
+            &data.tag,
+            assets.texture(&data.texture)?,
+            Pos::new(data.at[0], data.at[1]),
+            data.state.clone(),
+            data.flavor.iter().map(|s| s.as_str()).collect(),
+            link,
+        );
+        item.requires = data.requires.clone();
+        item.toggle = data.toggle.clone();
+        item.glow = data.glow;
+        item.journal = data.journal;
+        item.side = data.side.unwrap_or(match data.state {
+            ItemState::Interact => Side::Puzzles,
+            ItemState::Look => Side::Clues,
+            ItemState::Flavor if data.journal => Side::Clues,
+            _ => Side::Everyone,
+        });
+        Ok(item)
+    }
+}
+
+// Best completion time for every level that's been finished
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Progress {
+    pub best: HashMap<String, f32>,
+}
+
+impl Progress {
+    pub fn load() -> Self {
+        storage::load("campaign")
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+    pub fn complete(&mut self, level: &str, seconds: f32) {
+        let best = self.best.entry(level.to_string()).or_insert(seconds);
+        *best = best.min(seconds);
+        if let Ok(text) = serde_json::to_string_pretty(self) {
+            storage::save("campaign", &text);
+        }
+    }
+    // The first level is always open, after that each needs the one before
+    pub fn unlocked(&self, campaign: &[LevelInfo], index: usize) -> bool {
+        index == 0 || self.best.contains_key(&campaign[index - 1].id)
+    }
+}
+
+pub fn campaign(assets: &Assets) -> Result<Vec<LevelInfo>, String> {
+    let campaign: Vec<LevelInfo> =
+        serde_json::from_str(assets.data("campaign")).map_err(|e| format!("campaign: {}", e))?;
+    match campaign.is_empty() {
+        true => Err("The campaign has no levels".to_string()),
+        false => Ok(campaign),
+    }
+}
@@ -0,0 +1,330 @@
+use crate::assets::Assets;
+use crate::input::FrameInput;
+use crate::touch;
+use crate::ui::{self, tr};
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+// What each wheel of a lock cycles through, any run of characters (digits,
+// letters, whatever) or a set of named pictures
+
+#[derive(Clone)]
+pub enum Alphabet {
+    Text(Vec<char>),
+    Icons(Vec<(String, Texture2D)>),
+}
+
+impl Alphabet {
+    pub fn len(&self) -> usize {
+        match self {
+            Alphabet::Text(chars) => chars.len(),
+            Alphabet::Icons(icons) => icons.len(),
+        }
+    }
+    pub fn symbol(&self, index: usize) -> String {
+        match self {
+            Alphabet::Text(chars) => chars[index].to_string(),
+            Alphabet::Icons(icons) => icons[index].0.clone(),
+        }
+    }
+    pub fn index(&self, name: &str) -> Option<usize> {
+        (0..self.len()).find(|i| self.symbol(*i) == name)
+    }
+}
+
+// How an entry gets compared against the answer
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Validation {
+    // Same symbols on the same wheels
+    Exact,
+    // Same symbols in any order, how many times each shows up doesn't matter
+    AnyOrder,
+    // Same symbols in any order, each as many times as in the answer
+    Multiset,
+    // Every symbol of the answer is in there somewhere, the rest can be anything
+    Contains,
+}
+
+impl Validation {
+    pub fn check(&self, answer: &[usize], entry: &[usize]) -> bool {
+        match self {
+            Validation::Exact => answer == entry,
+            Validation::AnyOrder => {
+                answer.iter().all(|s| entry.contains(s)) && entry.iter().all(|s| answer.contains(s))
+            }
+            Validation::Multiset => answer.len() == entry.len() && matching(answer, entry) == answer.len(),
+            Validation::Contains => matching(answer, entry) == answer.len(),
+        }
+    }
+
+    // How close an entry got, without giving away which wheels are right
+    pub fn feedback(&self, answer: &[usize], entry: &[usize]) -> String {
+        match self {
+            Validation::Exact => {
+                let placed = answer.iter().zip(entry).filter(|(a, e)| a == e).count();
+                let misplaced = matching(answer, entry) - placed;
+                format!("{} {}, {} {}", placed, tr("right"), misplaced, tr("in the wrong place"))
+            }
+            Validation::AnyOrder => {
+                let mut wanted = answer.to_vec();
+                wanted.sort();
+                wanted.dedup();
+                let found = wanted.iter().filter(|s| entry.contains(s)).count();
+                format!("{} / {} {}", found, wanted.len(), tr("symbols right"))
+            }
+            Validation::Multiset | Validation::Contains => {
+                format!("{} / {} {}", matching(answer, entry), answer.len(), tr("symbols right"))
+            }
+        }
+    }
+}
+
+// How many of the answer's symbols the entry has, counting repeats
+fn matching(answer: &[usize], entry: &[usize]) -> usize {
+    let mut left = entry.to_vec();
+    answer
+        .iter()
+        .filter(|symbol| match left.iter().position(|s| s == *symbol) {
+            Some(i) => {
+                left.remove(i);
+                true
+            }
+            None => false,
+        })
+        .count()
+}
+
+// Where a wheel can be clicked, and where its current symbol gets drawn
+
+#[derive(Clone)]
+pub struct Wheel {
+    area: Rect,
+    symbol_at: Vec2,
+}
+
+impl Wheel {
+    pub fn new(area: Rect, symbol_at: Vec2) -> Self {
+        Wheel { area, symbol_at }
+    }
+}
+
+pub enum LockResult {
+    Correct,
+    Wrong,
+}
+
+// A lock described in a data file, with symbols and the answer given by name.
+// Symbols are either a string of characters or a list of named textures
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SymbolData {
+    Text(String),
+    Icons(Vec<IconData>),
+}
+
+#[derive(Deserialize)]
+struct LockData {
+    symbols: SymbolData,
+    wheels: Vec<WheelData>,
+    confirm: [f32; 4],
+    start: Vec<String>,
+    answer: Vec<String>,
+    validation: Validation,
+    // Where to say how close a wrong entry was, no feedback if left out
+    #[serde(default)]
+    feedback_at: Option<[f32; 2]>,
+    #[serde(default = "default_text_size")]
+    text_size: f32,
+    // Shown once the lock opens
+    #[serde(default)]
+    solved_text: Option<String>,
+}
+
+fn default_text_size() -> f32 {
+    80.0
+}
+
+#[derive(Deserialize)]
+struct IconData {
+    name: String,
+    texture: String,
+}
+
+#[derive(Deserialize)]
+struct WheelData {
+    area: [f32; 4],
+    at: [f32; 2],
+}
+
+// Any number of wheels and a confirm button. Clicking a wheel steps it
+// forward, right clicking steps it back, and the mouse wheel scrolls it
+// either way
+
+#[derive(Clone)]
+pub struct CombinationLock {
+    alphabet: Alphabet,
+    wheels: Vec<Wheel>,
+    confirm: Rect,
+    answer: Vec<usize>,
+    validation: Validation,
+    feedback_at: Option<Vec2>,
+    feedback: Option<String>,
+    pub entry: Vec<usize>,
+    text_size: f32,
+    pub solved_text: Option<String>,
+}
+
+impl CombinationLock {
+    // Symbols are named in the data, so a typo in one is a load error
+    pub fn from_data(data: &str, assets: &Assets) -> Result<Self, String> {
+        let data: LockData = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        let alphabet = match &data.symbols {
+            SymbolData::Text(text) => Alphabet::Text(text.chars().collect()),
+            SymbolData::Icons(icons) => {
+                let icons = icons.iter().map(|s| Ok((s.name.clone(), assets.texture(&s.texture)?)));
+                Alphabet::Icons(icons.collect::<Result<_, String>>()?)
+            }
+        };
+        let symbols = |names: &[String]| -> Result<Vec<usize>, String> {
+            names
+                .iter()
+                .map(|name| alphabet.index(name).ok_or_else(|| format!("{} isn't one of the lock's symbols", name)))
+                .collect()
+        };
+        let entry = symbols(&data.start)?;
+        let answer = symbols(&data.answer)?;
+        if entry.len() != data.wheels.len() {
+            return Err(format!("starts with {} symbols for {} wheels", entry.len(), data.wheels.len()));
+        }
+        let [x, y, w, h] = data.confirm;
+        Ok(CombinationLock {
+            wheels: data
+                .wheels
+                .iter()
+                .map(|wheel| {
+                    let [x, y, w, h] = wheel.area;
+                    Wheel::new(Rect::new(x, y, w, h), vec2(wheel.at[0], wheel.at[1]))
+                })
+                .collect(),
+            alphabet,
+            confirm: Rect::new(x, y, w, h),
+            answer,
+            validation: data.validation,
+            feedback_at: data.feedback_at.map(|[x, y]| vec2(x, y)),
+            feedback: None,
+            entry,
+            text_size: data.text_size,
+            solved_text: data.solved_text,
+        })
+    }
+
+    // A saved entry, as long as it fits this lock
+    pub fn restore(&mut self, entry: Vec<usize>) -> Result<(), String> {
+        if entry.len() != self.wheels.len() {
+            return Err(format!("entry has {} symbols for {} wheels", entry.len(), self.wheels.len()));
+        }
+        if let Some(symbol) = entry.iter().find(|s| **s >= self.alphabet.len()) {
+            return Err(format!("there's no symbol {} on the lock", symbol));
+        }
+        self.entry = entry;
+        self.feedback = None;
+        Ok(())
+    }
+
+    fn step(&mut self, wheel: usize, forward: bool) {
+        let len = self.alphabet.len();
+        self.entry[wheel] = match forward {
+            true => (self.entry[wheel] + 1) % len,
+            false => (self.entry[wheel] + len - 1) % len,
+        };
+        self.feedback = None;
+    }
+
+    pub fn update(&mut self, input: &FrameInput) -> Option<LockResult> {
+        let mouse = vec2(input.mouse.x, input.mouse.y);
+        let wheels = self.wheels.iter().enumerate().map(|(i, w)| (i, w.area));
+        let Some(wheel) = touch::target(wheels, mouse, input.touch) else {
+            if input.click().is_some() && self.confirm.contains(mouse) {
+                if self.validation.check(&self.answer, &self.entry) {
+                    return Some(LockResult::Correct);
+                }
+                if self.feedback_at.is_some() {
+                    self.feedback = Some(self.validation.feedback(&self.answer, &self.entry));
+                }
+                return Some(LockResult::Wrong);
+            }
+            return None;
+        };
+        if input.click().is_some() || input.wheel > 0f32 {
+            self.step(wheel, true);
+        } else if input.pressed.contains(&MouseButton::Right) || input.wheel < 0f32 {
+            self.step(wheel, false);
+        }
+        None
+    }
+
+    pub fn draw(&self) {
+        for (wheel, symbol) in self.wheels.iter().zip(&self.entry) {
+            let at = wheel.symbol_at;
+            match &self.alphabet {
+                Alphabet::Text(chars) => {
+                    draw_text(&chars[*symbol].to_string(), at.x, at.y, self.text_size, BLACK)
+                }
+                Alphabet::Icons(icons) => draw_texture(icons[*symbol].1, at.x, at.y, WHITE),
+            }
+        }
+        if let (Some(at), Some(feedback)) = (self.feedback_at, &self.feedback) {
+            ui::text(feedback, at.x, at.y, 40.0, YELLOW);
+        }
+    }
+
+    // The entry written out, for logging attempts
+    pub fn entry_string(&self) -> String {
+        let symbols: Vec<String> = self.entry.iter().map(|s| self.alphabet.symbol(*s)).collect();
+        match self.alphabet {
+            Alphabet::Text(_) => symbols.concat(),
+            Alphabet::Icons(_) => symbols.join(","),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSWER: &[usize] = &[1, 2, 2, 3];
+
+    #[test]
+    fn exact_wants_every_wheel_right() {
+        assert!(Validation::Exact.check(ANSWER, &[1, 2, 2, 3]));
+        assert!(!Validation::Exact.check(ANSWER, &[2, 1, 2, 3]));
+    }
+
+    #[test]
+    fn any_order_ignores_order_and_counts() {
+        assert!(Validation::AnyOrder.check(ANSWER, &[3, 2, 1, 1]));
+        assert!(!Validation::AnyOrder.check(ANSWER, &[3, 2, 1, 4]));
+        assert!(!Validation::AnyOrder.check(ANSWER, &[3, 3, 2, 2]));
+    }
+
+    #[test]
+    fn multiset_counts_repeats() {
+        assert!(Validation::Multiset.check(ANSWER, &[2, 3, 1, 2]));
+        assert!(!Validation::Multiset.check(ANSWER, &[3, 2, 1, 1]));
+        assert!(!Validation::Multiset.check(ANSWER, &[1, 2, 2, 3, 3]));
+    }
+
+    #[test]
+    fn contains_allows_extras() {
+        assert!(Validation::Contains.check(ANSWER, &[0, 2, 3, 1, 2]));
+        assert!(!Validation::Contains.check(ANSWER, &[0, 2, 3, 1, 0]));
+    }
+
+    #[test]
+    fn feedback_splits_placed_from_misplaced() {
+        assert_eq!(Validation::Exact.feedback(ANSWER, &[1, 3, 2, 0]), "2 right, 1 in the wrong place");
+    }
+}
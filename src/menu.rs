@@ -0,0 +1,592 @@
+use crate::achievements::Achievements;
+use crate::assets::Assets;
+use crate::audio::Audio;
+use crate::endings::Endings;
+use crate::game::Game;
+use crate::input::{FrameInput, InputSource, Recorder, Recording};
+use crate::level::{self, LevelInfo, Progress};
+use crate::net::{self, Client, Host, Net};
+use crate::settings::Settings;
+use crate::storage;
+use crate::telemetry::Telemetry;
+use crate::ui;
+use macroquad::prelude::*;
+
+#[derive(PartialEq, Clone, Copy)]
+enum Screen {
+    Title,
+    Levels,
+    Coop,
+    Endings,
+    Achievements,
+    Settings,
+    Playing,
+    Paused,
+}
+
+// Everything around a game session: the title screen, picking a level,
+// pausing, settings, plus where a session's input and telemetry get written
+
+pub struct App {
+    assets: Assets,
+    audio: Audio,
+    settings: Settings,
+    screen: Screen,
+    // Where to go back to from the settings screen
+    settings_from: Screen,
+    game: Option<Game>,
+    endings: Endings,
+    achievements: Achievements,
+    campaign: Vec<LevelInfo>,
+    progress: Progress,
+    input: InputSource,
+    recorder: Option<Recorder>,
+    telemetry: Telemetry,
+    record: bool,
+    log_telemetry: bool,
+    // Nothing gets saved while someone else's recording plays
+    replay_muted: bool,
+    // Co-op, once hosting or joined, the address being typed in and how the
+    // last attempt went
+    net: Option<Net>,
+    address: String,
+    // Hosting with the clues and puzzles split between players
+    split_roles: bool,
+    net_status: String,
+    // Why the last level didn't load, shown on the title and level screens
+    load_error: String,
+    // The save Continue picks up, read once rather than every title frame
+    save: Option<String>,
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    watcher: crate::reload::Watcher,
+    pub quit: bool,
+}
+
+// Minutes and seconds, like 3:07
+
+fn format_time(seconds: f32) -> String {
+    format!("{}:{:02}", (seconds / 60f32) as u32, (seconds % 60f32) as u32)
+}
+
+// Which level a save belongs to, without restoring the whole thing
+
+fn save_level(save: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(save).ok()?;
+    value.get("level")?.as_str().map(|level| level.to_string())
+}
+
+impl App {
+    pub fn new(assets: Assets, audio: Audio, record: bool, log_telemetry: bool) -> Self {
+        let settings = Settings::load();
+        settings.apply(&assets);
+        prevent_quit();
+        // Touches get turned into clicks and swipes by hand, see touch.rs
+        simulate_mouse_with_touch(false);
+        // Without a campaign there's nothing to start, which the title says
+        let (campaign, load_error) = match level::campaign(&assets) {
+            Ok(campaign) => (campaign, String::new()),
+            Err(e) => {
+                eprintln!("Couldn't load the campaign: {}", e);
+                (Vec::new(), format!("{}: {}", ui::tr("Couldn't load the campaign"), e))
+            }
+        };
+        App {
+            campaign,
+            progress: Progress::load(),
+            endings: Endings::new(assets.data("endings")),
+            achievements: Achievements::new(assets.data("achievements")),
+            assets,
+            audio,
+            settings,
+            screen: Screen::Title,
+            settings_from: Screen::Title,
+            game: None,
+            input: InputSource::live(),
+            recorder: None,
+            telemetry: Telemetry::new(None),
+            record,
+            log_telemetry,
+            replay_muted: false,
+            net: None,
+            address: net::DEFAULT_ADDRESS.to_string(),
+            split_roles: false,
+            net_status: String::new(),
+            load_error,
+            save: storage::load("savegame"),
+            #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+            watcher: crate::reload::Watcher::new(),
+            quit: false,
+        }
+    }
+
+    // Jumps straight into a recorded session, it's already been logged once
+    // so nothing gets written while it plays, saves included until the
+    // player takes over
+    // Recordings from before there were levels are all from the first one
+    pub async fn replay(&mut self, recording: Recording) {
+        self.record = false;
+        self.log_telemetry = false;
+        self.replay_muted = true;
+        storage::disable_writes();
+        let Some(level) = recording.level.or(self.first_level()) else { return };
+        self.start(&level, recording.save).await;
+        self.input = InputSource::replay(recording.frames);
+    }
+
+    fn first_level(&self) -> Option<String> {
+        self.campaign.first().map(|info| info.id.clone())
+    }
+
+    // A level that doesn't load leaves the player where they were, or on the
+    // title if that was a game, with what went wrong on screen
+    async fn start(&mut self, level: &str, save: Option<String>) {
+        let loaded = match self.assets.load_level(level).await {
+            Ok(()) => Game::new(&self.assets),
+            Err(e) => Err(e),
+        };
+        let mut game = match loaded {
+            Ok(game) => game,
+            Err(e) => {
+                eprintln!("Couldn't load level {}: {}", level, e);
+                self.load_error = format!("{} {}: {}", ui::tr("Couldn't load level"), level, e);
+                self.game = None;
+                self.recorder = None;
+                self.net = None;
+                if !matches!(self.screen, Screen::Title | Screen::Levels) {
+                    self.screen = Screen::Title;
+                }
+                return;
+            }
+        };
+        self.load_error = String::new();
+        game.set_colorblind(self.settings.colorblind);
+        if let Some(net) = &self.net {
+            game.set_role(net.role());
+        }
+        if let Some(text) = &save {
+            if let Err(e) = game.restore(text) {
+                eprintln!("Couldn't load save, starting over: {}", e);
+            }
+        }
+        if let Some(Net::Host(host)) = &mut self.net {
+            host.restart(&game, &self.assets);
+        }
+        let session = crate::unix_time();
+        // Other players change things in a co-op game, so it couldn't be replayed
+        self.recorder = match self.record && self.net.is_none() {
+            true => {
+                Recorder::create(&format!("recordings/session-{}.rec", session), level, save.as_deref())
+            }
+            false => None,
+        };
+        self.telemetry = match self.log_telemetry {
+            true => Telemetry::new(Some(&format!("telemetry/session-{}.jsonl", session))),
+            false => Telemetry::new(None),
+        };
+        self.game = Some(game);
+        self.screen = Screen::Playing;
+    }
+
+    fn save_game(&mut self) {
+        let Some(game) = &self.game else { return };
+        // A joined game is the host's to save
+        if self.input.replaying() || matches!(self.net, Some(Net::Client(_))) {
+            return;
+        }
+        if game.finished() {
+            storage::remove("savegame");
+        } else {
+            storage::save("savegame", &game.save_data());
+        }
+        // Writes can be off, so this is whatever actually got saved
+        self.save = storage::load("savegame");
+    }
+
+    pub async fn frame(&mut self) {
+        if self.replay_muted && !self.input.replaying() {
+            storage::enable_writes();
+            self.replay_muted = false;
+        }
+        let input = self.input.next();
+
+        if is_quit_requested() {
+            self.save_game();
+            self.quit = true;
+            return;
+        }
+
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        self.hot_reload(input.dt).await;
+
+        match self.screen {
+            Screen::Title => self.title(&input).await,
+            Screen::Levels => self.levels(&input).await,
+            Screen::Coop => self.coop(&input).await,
+            Screen::Endings => {
+                clear_background(BLACK);
+                self.endings.draw_gallery();
+                if input.click().is_some() || input.key(KeyCode::Escape) {
+                    self.screen = Screen::Title;
+                }
+            }
+            Screen::Achievements => {
+                clear_background(BLACK);
+                self.achievements.draw_list();
+                if input.click().is_some() || input.key(KeyCode::Escape) {
+                    self.screen = Screen::Title;
+                }
+            }
+            Screen::Settings => self.settings(&input),
+            Screen::Playing => self.play(&input).await,
+            Screen::Paused => self.pause(&input),
+        }
+        self.network(&input).await;
+    }
+
+    // Dev builds pick up edits to the level, its scripts and textures while
+    // the game runs. Anything that doesn't load or build leaves the game and
+    // its assets as they were, so a half saved file can't take it down
+
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    async fn hot_reload(&mut self, dt: f32) {
+        let changed = self.watcher.changed(dt);
+        if changed.is_empty() || self.input.replaying() {
+            return;
+        }
+        let Some(game) = &self.game else { return };
+        let assets = match self.assets.reloaded(&changed).await {
+            Ok(assets) => assets,
+            Err(e) => {
+                eprintln!("Couldn't reload assets: {}", e);
+                return;
+            }
+        };
+        match game.reloaded(&assets) {
+            Ok(game) => {
+                self.game = Some(game);
+                self.assets = assets;
+                println!("Reloaded {}", self.assets.level_id());
+            }
+            Err(e) => eprintln!("Couldn't carry the game over to the reloaded level: {}", e),
+        }
+    }
+
+    async fn title(&mut self, input: &FrameInput) {
+        clear_background(Color::new(63f32 / 255f32, 72f32 / 255f32, 87f32 / 255f32, 1f32));
+        ui::text(&self.load_error, 20.0, 570.0, 24.0, GRAY);
+        let options = [
+            ("New Game".to_string(), !self.campaign.is_empty()),
+            ("Continue".to_string(), self.save.is_some()),
+            ("Levels".to_string(), true),
+            // No sockets in the browser
+            ("Co-op".to_string(), !cfg!(target_arch = "wasm32")),
+            ("Endings".to_string(), true),
+            ("Achievements".to_string(), true),
+            ("Settings".to_string(), true),
+            ("Quit".to_string(), true),
+        ];
+        match ui::menu("Escape Room", &options, input) {
+            Some(0) => {
+                let Some(first) = self.first_level() else { return };
+                self.start(&first, None).await;
+            }
+            Some(1) => {
+                let Some(save) = self.save.clone() else { return };
+                match save_level(&save) {
+                    Some(level) => self.start(&level, Some(save)).await,
+                    None => eprintln!("Couldn't tell which level the save is from"),
+                }
+            }
+            Some(2) => self.screen = Screen::Levels,
+            Some(3) => self.screen = Screen::Coop,
+            Some(4) => {
+                self.endings = Endings::new(self.assets.data("endings"));
+                self.screen = Screen::Endings;
+            }
+            Some(5) => {
+                self.achievements = Achievements::new(self.assets.data("achievements"));
+                self.screen = Screen::Achievements;
+            }
+            Some(6) => {
+                self.settings_from = Screen::Title;
+                self.screen = Screen::Settings;
+            }
+            Some(7) => self.quit = true,
+            _ => (),
+        }
+    }
+
+    // Every level in order with its best time, each one opens up once the
+    // one before it has been finished
+    async fn levels(&mut self, input: &FrameInput) {
+        clear_background(Color::new(63f32 / 255f32, 72f32 / 255f32, 87f32 / 255f32, 1f32));
+        let mut options: Vec<(String, bool)> = Vec::new();
+        for (i, info) in self.campaign.iter().enumerate() {
+            let unlocked = self.progress.unlocked(&self.campaign, i);
+            let status = match self.progress.best.get(&info.id) {
+                Some(best) => format!("{} {}", ui::tr("Best"), format_time(*best)),
+                None if unlocked => ui::tr("New"),
+                None => ui::tr("Locked"),
+            };
+            options.push((format!("{} - {}", ui::tr(&info.title), status), unlocked));
+        }
+        options.push(("Back".to_string(), true));
+        ui::text(&self.load_error, 20.0, 570.0, 24.0, GRAY);
+
+        match ui::menu("Levels", &options, input) {
+            Some(i) if i < self.campaign.len() => {
+                let level = self.campaign[i].id.clone();
+                self.start(&level, None).await;
+            }
+            Some(_) => self.screen = Screen::Title,
+            None => (),
+        }
+        if input.key(KeyCode::Escape) {
+            self.screen = Screen::Title;
+        }
+    }
+
+    // Hosting or joining a game over the network, by address
+
+    async fn coop(&mut self, input: &FrameInput) {
+        clear_background(Color::new(63f32 / 255f32, 72f32 / 255f32, 87f32 / 255f32, 1f32));
+        let waiting = self.net.is_some();
+        if !waiting {
+            if input.key(KeyCode::Backspace) {
+                self.address.pop();
+            }
+            for c in &input.chars {
+                if c.is_ascii_alphanumeric() || ".:-[]".contains(*c) {
+                    self.address.push(*c);
+                }
+            }
+        }
+        let address = format!("{}: {}", ui::tr("Address"), self.address);
+        let size = measure_text(&address, None, 30, 1f32);
+        draw_text(&address, 400.0 - size.width / 2.0, 155.0, 30.0, WHITE);
+        ui::text(&self.net_status, 20.0, 570.0, 24.0, GRAY);
+
+        let mode = match self.split_roles {
+            true => ui::tr("Clues and puzzles"),
+            false => ui::tr("Together"),
+        };
+        let options = [
+            ("Host".to_string(), !waiting),
+            ("Join".to_string(), !waiting),
+            (format!("{}: {}", ui::tr("Mode"), mode), !waiting),
+            ("Back".to_string(), true),
+        ];
+        let address = self.address.clone();
+        match ui::menu("Co-op", &options, input) {
+            Some(0) => self.host(&address, self.split_roles).await,
+            Some(1) => self.join(&address),
+            Some(2) => self.split_roles = !self.split_roles,
+            Some(_) => self.leave_coop(),
+            None => (),
+        }
+        if input.key(KeyCode::Escape) {
+            self.leave_coop();
+        }
+    }
+
+    // Starts the campaign over for others to join. With split roles the host
+    // holds the clues and whoever joins solves the puzzles
+    pub async fn host(&mut self, address: &str, split_roles: bool) {
+        let Some(first) = self.first_level() else { return };
+        match Host::start(address, split_roles) {
+            Ok(host) => {
+                self.net = Some(Net::Host(host));
+                self.net_status = String::new();
+                self.start(&first, None).await;
+            }
+            Err(e) => {
+                eprintln!("Couldn't host on {}: {}", address, e);
+                self.net_status = format!("{} {}: {}", ui::tr("Couldn't host on"), address, e);
+                self.screen = Screen::Coop;
+            }
+        }
+    }
+
+    // The game itself starts once the host says which level it's on
+    pub fn join(&mut self, address: &str) {
+        self.screen = Screen::Coop;
+        match Client::join(address) {
+            Ok(client) => {
+                self.net = Some(Net::Client(client));
+                self.net_status = ui::tr("Waiting for the host...");
+            }
+            Err(e) => {
+                eprintln!("Couldn't join {}: {}", address, e);
+                self.net_status = format!("{} {}: {}", ui::tr("Couldn't join"), address, e);
+            }
+        }
+    }
+
+    fn leave_coop(&mut self) {
+        self.net = None;
+        self.net_status = String::new();
+        self.screen = Screen::Title;
+    }
+
+    // Keeps a co-op game in step every frame, paused or not, so one player
+    // stopping doesn't hold up the rest
+    async fn network(&mut self, input: &FrameInput) {
+        let Some(net) = &mut self.net else { return };
+        let welcome = match net {
+            Net::Host(host) => {
+                if let Some(game) = &mut self.game {
+                    host.update(game, input.mouse, &self.assets);
+                }
+                None
+            }
+            Net::Client(client) => client.update(self.game.as_mut()),
+        };
+        if let Some(Net::Client(client)) = &self.net {
+            if client.lost() {
+                eprintln!("Lost the connection to the host");
+                self.game = None;
+                self.net = None;
+                self.net_status = ui::tr("Lost the connection to the host");
+                self.screen = Screen::Coop;
+                return;
+            }
+        }
+        if let Some(welcome) = welcome {
+            self.start(&welcome.level, None).await;
+            if let Some(game) = &mut self.game {
+                if let Err(e) = game.apply_shared_state(&welcome.state) {
+                    eprintln!("Couldn't take on the host's state: {}", e);
+                }
+            }
+        }
+    }
+
+    // Records the time for the level just finished and moves on to the next
+    // one, or back to the level list after the last
+    async fn next_level(&mut self) {
+        let Some(game) = self.game.take() else { return };
+        self.progress.complete(game.level(), game.finish_time());
+        storage::remove("savegame");
+        self.save = storage::load("savegame");
+        self.recorder = None;
+        let index = self.campaign.iter().position(|info| info.id == game.level());
+        match index.and_then(|i| self.campaign.get(i + 1)) {
+            Some(next) => {
+                let next = next.id.clone();
+                self.start(&next, None).await;
+            }
+            None => self.screen = Screen::Levels,
+        }
+    }
+
+    async fn play(&mut self, input: &FrameInput) {
+        let Some(game) = &mut self.game else { return };
+
+        // Whoever joined moves on when the host does
+        let joined = matches!(self.net, Some(Net::Client(_)));
+        if game.ending_shown() && input.click().is_some() && !self.input.replaying() && !joined {
+            self.next_level().await;
+            return;
+        }
+
+        let chatting = self.net.as_mut().and_then(|net| net.chat_input(input, game));
+        let input = chatting.as_ref().unwrap_or(input);
+
+        if input.key(KeyCode::Escape) && !self.input.replaying() {
+            self.screen = Screen::Paused;
+            self.save_game();
+            return;
+        }
+
+        game.update(input);
+        if let Some(Net::Client(client)) = &mut self.net {
+            client.send(input, game);
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.write(input);
+        }
+        self.telemetry.write(game.take_events());
+        for sound in game.take_sounds() {
+            self.audio.play(&sound, self.settings.volume);
+        }
+        game.draw();
+        if let Some(net) = &self.net {
+            net.draw(game);
+        }
+    }
+
+    // The game stays on screen underneath, but nothing updates so every
+    // timer in it stays put
+    fn pause(&mut self, input: &FrameInput) {
+        if let Some(game) = &self.game {
+            game.draw();
+            if let Some(net) = &self.net {
+                net.draw(game);
+            }
+        }
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0f32, 0f32, 0f32, 0.7));
+
+        if input.key(KeyCode::Escape) {
+            self.screen = Screen::Playing;
+            return;
+        }
+        let options = [
+            ("Resume".to_string(), true),
+            ("Settings".to_string(), true),
+            ("Quit to Title".to_string(), true),
+            ("Quit Game".to_string(), true),
+        ];
+        match ui::menu("Paused", &options, input) {
+            Some(0) => self.screen = Screen::Playing,
+            Some(1) => {
+                self.settings_from = Screen::Paused;
+                self.screen = Screen::Settings;
+            }
+            Some(2) => {
+                self.save_game();
+                self.game = None;
+                self.recorder = None;
+                self.net = None;
+                self.screen = Screen::Title;
+            }
+            Some(3) => {
+                self.save_game();
+                self.quit = true;
+            }
+            _ => (),
+        }
+    }
+
+    fn settings(&mut self, input: &FrameInput) {
+        clear_background(Color::new(63f32 / 255f32, 72f32 / 255f32, 87f32 / 255f32, 1f32));
+        let on_off = |on: bool| if on { ui::tr("On") } else { ui::tr("Off") };
+        let options = [
+            (format!("{}: {}%", ui::tr("Volume"), (self.settings.volume * 100f32).round()), true),
+            (format!("{}: {}", ui::tr("Fullscreen"), on_off(self.settings.fullscreen)), true),
+            (format!("{}: {}%", ui::tr("Text size"), (self.settings.text_size * 100f32).round()), true),
+            (format!("{}: {}", ui::tr("Language"), self.settings.language_name()), true),
+            (format!("{}: {}", ui::tr("Colorblind mode"), on_off(self.settings.colorblind)), true),
+            ("Back".to_string(), true),
+        ];
+        let picked = ui::menu("Settings", &options, input);
+        match picked {
+            Some(0) => self.settings.next_volume(),
+            Some(1) => self.settings.fullscreen = !self.settings.fullscreen,
+            Some(2) => self.settings.next_text_size(),
+            Some(3) => self.settings.next_language(),
+            Some(4) => self.settings.colorblind = !self.settings.colorblind,
+            Some(5) => self.screen = self.settings_from,
+            _ => (),
+        }
+        if input.key(KeyCode::Escape) {
+            self.screen = self.settings_from;
+        }
+        if matches!(picked, Some(0..=4)) {
+            self.settings.apply(&self.assets);
+            self.settings.save();
+            if let Some(game) = &mut self.game {
+                game.set_colorblind(self.settings.colorblind);
+            }
+        }
+    }
+}
@@ -0,0 +1,601 @@
+use crate::assets::Assets;
+use crate::chat::Chat;
+use crate::game::Game;
+use crate::input::FrameInput;
+use crate::level::Side;
+use crate::ui;
+use crate::Pos;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+// Where co-op games get hosted and joined unless told otherwise
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+
+// Longest a message can get, a whole level's state fits many times over.
+// Anything longer is a peer that's broken or up to no good
+
+const MAX_LINE: usize = 1 << 20;
+
+// Cursor colors by player number, the host is always the first
+
+const COLORS: [Color; 4] = [RED, BLUE, DARKGREEN, ORANGE];
+
+pub fn color(id: usize) -> Color {
+    COLORS[id % COLORS.len()]
+}
+
+// Everything sent between games, one JSON object per line
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Message {
+    // From a player to the host, a frame of input as a recording line, and a
+    // checksum of the flags and puzzles the player ended up with after it
+    Input { line: String, state: u64 },
+    // From the host: which player this is, what they get to see and the
+    // level being played, sent on joining and again whenever the host moves
+    // on to another level
+    Welcome { id: usize, role: Side, level: String, state: String },
+    // Flags and puzzles changed
+    State { state: String },
+    // Where everyone is, every frame
+    Players { players: Vec<Presence> },
+    // Either way, the host passes it on to everyone else
+    Chat { from: usize, text: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Presence {
+    pub id: usize,
+    pub x: f32,
+    pub y: f32,
+    // What's on their screen, see Game::view_name
+    pub view: String,
+    // Label of the room they're in, for the list of players
+    pub room: String,
+}
+
+// Short enough to send every frame, for checking two games agree. It's
+// FNV-1a, written out here so builds from any Rust version agree on it
+
+fn checksum(state: &str) -> u64 {
+    state.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+impl Presence {
+    fn of(id: usize, game: &Game, mouse: Pos) -> Self {
+        Presence { id, x: mouse.x, y: mouse.y, view: game.view_name(), room: game.room_label() }
+    }
+}
+
+// A socket that never blocks the frame. Whatever doesn't go out or hasn't
+// fully come in yet waits for the next frame
+
+struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    closed: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Connection { stream, incoming: Vec::new(), outgoing: Vec::new(), closed: false })
+    }
+
+    fn send(&mut self, message: &Message) {
+        let mut line = serde_json::to_vec(message).unwrap();
+        line.push(b'\n');
+        self.outgoing.extend(line);
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        while !self.outgoing.is_empty() && !self.closed {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.closed = true,
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(_) => self.closed = true,
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Vec<Message> {
+        let mut buffer = [0u8; 4096];
+        // Whatever's left past the cap waits for the next frame
+        while !self.closed && self.incoming.len() <= MAX_LINE {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(n) => self.incoming.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(_) => self.closed = true,
+            }
+        }
+        let mut messages = Vec::new();
+        while let Some(end) = self.incoming.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            match serde_json::from_slice(&line) {
+                Ok(message) => messages.push(message),
+                Err(e) => eprintln!("Ignoring a message that didn't make sense: {}", e),
+            }
+        }
+        if self.incoming.len() > MAX_LINE {
+            eprintln!("Dropping a connection that sent a message too long to be real");
+            self.incoming.clear();
+            self.closed = true;
+        }
+        messages
+    }
+}
+
+// Another player, as the host sees them. Their input gets played into a game
+// of their own here, so the host works out everything they do
+
+struct Remote {
+    id: usize,
+    connection: Connection,
+    game: Game,
+    role: Side,
+    held: Vec<MouseButton>,
+    mouse: Pos,
+    // Their game went somewhere the host's didn't, so they need setting straight
+    stale: bool,
+}
+
+impl Remote {
+    fn welcome(&mut self, game: &Game) {
+        self.connection.send(&Message::Welcome {
+            id: self.id,
+            role: self.role,
+            level: game.level().to_string(),
+            state: game.shared_state(),
+        });
+    }
+
+    // A fresh copy of the host's game for them to play into, and a welcome
+    // so their own game starts over to match
+    fn restart(&mut self, game: &Game, assets: &Assets) {
+        match copy(assets, self.role) {
+            Ok(copy) => self.game = copy,
+            Err(e) => {
+                eprintln!("Couldn't start player {}'s game over, letting them go: {}", self.id, e);
+                self.connection.closed = true;
+                return;
+            }
+        }
+        self.held.clear();
+        self.welcome(game);
+    }
+}
+
+// The host's copy of a player's game, which is the host's level so it
+// loads as long as the host's own game did
+fn copy(assets: &Assets, role: Side) -> Result<Game, String> {
+    let mut game = Game::new(assets)?;
+    game.set_role(role);
+    game.set_keep_records(false);
+    Ok(game)
+}
+
+// The game everyone else joins. Its flags and puzzles are the real ones,
+// players only ever send what they pressed and get told what happened
+
+pub struct Host {
+    listener: TcpListener,
+    remotes: Vec<Remote>,
+    next_id: usize,
+    // Shared state as everyone was last told it
+    sent: String,
+    players: Vec<Presence>,
+    // With roles split the host holds the clues, and whoever joins takes
+    // turns between solving and holding clues too
+    split: bool,
+    chat: Chat,
+}
+
+impl Host {
+    pub fn start(address: &str, split: bool) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Host {
+            listener,
+            remotes: Vec::new(),
+            next_id: 1,
+            sent: String::new(),
+            players: Vec::new(),
+            split,
+            chat: Chat::new(),
+        })
+    }
+
+    fn role(&self, id: usize) -> Side {
+        match (self.split, id % 2) {
+            (false, _) => Side::Everyone,
+            (true, 0) => Side::Clues,
+            (true, _) => Side::Puzzles,
+        }
+    }
+
+    pub fn joined(&self) -> usize {
+        self.remotes.len()
+    }
+
+    // Lets new players in, plays what they did into the shared game and tells
+    // everyone how things stand
+    pub fn update(&mut self, game: &mut Game, mouse: Pos, assets: &Assets) {
+        while let Ok((stream, address)) = self.listener.accept() {
+            let connection = match Connection::new(stream) {
+                Ok(connection) => connection,
+                Err(e) => {
+                    eprintln!("Couldn't set up the connection from {}: {}", address, e);
+                    continue;
+                }
+            };
+            let role = self.role(self.next_id);
+            let copy = match copy(assets, role) {
+                Ok(copy) => copy,
+                Err(e) => {
+                    eprintln!("Couldn't set up a game for {}: {}", address, e);
+                    continue;
+                }
+            };
+            let mut remote = Remote {
+                id: self.next_id,
+                connection,
+                game: copy,
+                role,
+                held: Vec::new(),
+                mouse: Pos::new(0f32, 0f32),
+                stale: false,
+            };
+            self.next_id += 1;
+            remote.welcome(game);
+            if cfg!(debug_assertions) {
+                println!("Player {} joined from {}", remote.id, address);
+            }
+            self.remotes.push(remote);
+        }
+
+        let mut said = Vec::new();
+        for remote in &mut self.remotes {
+            for message in remote.connection.receive() {
+                let (line, checked) = match message {
+                    Message::Input { line, state } => (line, state),
+                    Message::Chat { text, .. } => {
+                        said.extend(Chat::clean(&text).map(|text| (remote.id, text)));
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let Some(input) = FrameInput::from_line(&line, &mut remote.held) else {
+                    eprintln!("Player {} sent input that didn't make sense", remote.id);
+                    continue;
+                };
+                remote.mouse = input.mouse;
+
+                // Whatever they did happens on top of the latest shared state,
+                // and anything it changed becomes the new shared state
+
+                let shared = game.shared_state();
+                if remote.game.shared_state() != shared {
+                    if let Err(e) = remote.game.apply_shared_state(&shared) {
+                        eprintln!("Player {}'s game fell out with the host's, starting it over: {}", remote.id, e);
+                        remote.restart(game, assets);
+                        continue;
+                    }
+                }
+                remote.game.update(&input);
+                remote.game.take_events();
+                remote.game.take_sounds();
+                let after = remote.game.shared_state();
+                if after != shared {
+                    if let Err(e) = game.apply_shared_state(&after) {
+                        eprintln!("Couldn't take on player {}'s changes: {}", remote.id, e);
+                    }
+                }
+
+                // Anything their own game did that the host didn't, like a
+                // click the host turned down, gets rolled back
+                remote.stale = checksum(&after) != checked;
+            }
+        }
+        for (from, text) in said {
+            self.chat.add(from, &text);
+            self.relay(from, &text);
+        }
+        // Comings and goings are only worth printing while working on the game
+        if cfg!(debug_assertions) {
+            for remote in self.remotes.iter().filter(|r| r.connection.closed) {
+                println!("Player {} left", remote.id);
+            }
+        }
+        self.remotes.retain(|r| !r.connection.closed);
+
+        let state = game.shared_state();
+        let changed = state != self.sent;
+        let mut players = vec![Presence::of(0, game, mouse)];
+        players.extend(self.remotes.iter().map(|r| Presence::of(r.id, &r.game, r.mouse)));
+        for remote in &mut self.remotes {
+            if changed || remote.stale {
+                remote.connection.send(&Message::State { state: state.clone() });
+                remote.stale = false;
+            }
+            remote.connection.send(&Message::Players { players: players.clone() });
+        }
+        self.sent = state;
+        self.players = players;
+    }
+
+    // Sends a line to everyone but whoever said it
+    fn relay(&mut self, from: usize, text: &str) {
+        for remote in self.remotes.iter_mut().filter(|r| r.id != from) {
+            remote.connection.send(&Message::Chat { from, text: text.to_string() });
+        }
+    }
+
+    // The host moved to another level, everyone follows with a fresh game
+    pub fn restart(&mut self, game: &Game, assets: &Assets) {
+        for remote in &mut self.remotes {
+            remote.restart(game, assets);
+        }
+        self.sent = game.shared_state();
+    }
+}
+
+// What joining a host hands back when there's a level to start
+
+pub struct Welcome {
+    pub level: String,
+    pub state: String,
+}
+
+// A game joined from somewhere else. It runs as normal so it feels the same
+// to play, but the host's flags and puzzles always win
+
+pub struct Client {
+    connection: Connection,
+    id: usize,
+    role: Side,
+    players: Vec<Presence>,
+    chat: Chat,
+}
+
+impl Client {
+    pub fn join(address: &str) -> std::io::Result<Self> {
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, "no such address"))?;
+        let stream = TcpStream::connect_timeout(&address, Duration::from_secs(3))?;
+        Ok(Client {
+            connection: Connection::new(stream)?,
+            id: 0,
+            role: Side::Everyone,
+            players: Vec::new(),
+            chat: Chat::new(),
+        })
+    }
+
+    pub fn role(&self) -> Side {
+        self.role
+    }
+
+    pub fn lost(&self) -> bool {
+        self.connection.closed
+    }
+
+    // What was pressed, with where the game ended up after it for the host
+    // to check against
+    pub fn send(&mut self, input: &FrameInput, game: &Game) {
+        let state = checksum(&game.shared_state());
+        self.connection.send(&Message::Input { line: input.to_line(), state });
+    }
+
+    // Takes on whatever the host says, handing back a level to start if the
+    // host started one
+    pub fn update(&mut self, mut game: Option<&mut Game>) -> Option<Welcome> {
+        let mut welcome = None;
+        for message in self.connection.receive() {
+            match message {
+                Message::Welcome { id, role, level, state } => {
+                    self.id = id;
+                    self.role = role;
+                    welcome = Some(Welcome { level, state });
+                }
+                // Anything after a welcome is newer than the welcome's own
+                // state, and the game it's for hasn't been started yet
+                Message::State { state } => match (&mut welcome, game.as_deref_mut()) {
+                    (Some(welcome), _) => welcome.state = state,
+                    (None, Some(game)) => {
+                        if let Err(e) = game.apply_shared_state(&state) {
+                            eprintln!("Couldn't take on the host's state: {}", e);
+                        }
+                    }
+                    (None, None) => (),
+                },
+                Message::Players { players } => self.players = players,
+                Message::Chat { from, text } => self.chat.add(from, &text),
+                Message::Input { .. } => (),
+            }
+        }
+        self.connection.flush();
+        welcome
+    }
+}
+
+// Either end of a co-op game, which look the same once playing
+
+pub enum Net {
+    Host(Host),
+    Client(Client),
+}
+
+impl Net {
+    // What this player gets to see
+    pub fn role(&self) -> Side {
+        match self {
+            Net::Host(host) => host.role(0),
+            Net::Client(client) => client.role(),
+        }
+    }
+
+    pub fn chat(&mut self) -> &mut Chat {
+        match self {
+            Net::Host(host) => &mut host.chat,
+            Net::Client(client) => &mut client.chat,
+        }
+    }
+
+    pub fn say(&mut self, text: &str) {
+        match self {
+            Net::Host(host) => {
+                host.chat.add(0, text);
+                host.relay(0, text);
+            }
+            Net::Client(client) => {
+                client.chat.add(client.id, text);
+                client.connection.send(&Message::Chat { from: client.id, text: text.to_string() });
+            }
+        }
+    }
+
+    // Chat gets the keyboard first. While it's open the game carries on, but
+    // only sees the mouse move, so this hands back what the game should get
+    pub fn chat_input(&mut self, input: &FrameInput, game: &Game) -> Option<FrameInput> {
+        let start = input.key(KeyCode::Enter) && !game.typing();
+        let was_open = self.chat().open();
+        if let Some(text) = self.chat().update(input, start) {
+            self.say(&text);
+        }
+        if !was_open && !self.chat().open() {
+            return None;
+        }
+        let mut quiet = FrameInput::empty(input.frame);
+        quiet.dt = input.dt;
+        quiet.mouse = input.mouse;
+        quiet.touch = input.touch;
+        Some(quiet)
+    }
+    fn others(&self) -> impl Iterator<Item = &Presence> {
+        let (players, me) = match self {
+            Net::Host(host) => (&host.players, 0),
+            Net::Client(client) => (&client.players, client.id),
+        };
+        players.iter().filter(move |p| p.id != me)
+    }
+
+    // Other players' cursors when they're looking at the same thing, and a
+    // list of where everyone is
+    pub fn draw(&self, game: &Game) {
+        let view = game.view_name();
+        let role = match self.role() {
+            Side::Clues => Some("You hold the clues"),
+            Side::Puzzles => Some("You solve the puzzles"),
+            Side::Everyone => None,
+        };
+        let top = if role.is_some() { 102.0 } else { 80.0 };
+        if let Some(role) = role {
+            draw_rectangle(600.0, 64.0, 200.0, 22.0, Color::new(0f32, 0f32, 0f32, 0.5));
+            ui::text(role, 610.0, 80.0, 20.0, YELLOW);
+        }
+        for (i, player) in self.others().enumerate() {
+            let color = color(player.id);
+            if player.view == view {
+                draw_circle(player.x, player.y, 6f32, color);
+                draw_circle_lines(player.x, player.y, 6f32, 2f32, WHITE);
+                draw_text(&format!("P{}", player.id + 1), player.x + 8.0, player.y - 8.0, 20.0, color);
+            }
+            let line = format!("P{}: {}", player.id + 1, player.room);
+            let y = top + i as f32 * 22.0;
+            draw_rectangle(600.0, y - 16.0, 200.0, 22.0, Color::new(0f32, 0f32, 0f32, 0.5));
+            draw_text(&line, 610.0, y, 20.0, color);
+        }
+        match self {
+            Net::Host(host) => host.chat.draw(),
+            Net::Client(client) => client.chat.draw(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::MAX_LENGTH;
+
+    fn room() -> (Game, Assets) {
+        let mut assets = Assets::load_headless();
+        assets.load_level_headless("room").unwrap();
+        (Game::new(&assets).unwrap(), assets)
+    }
+
+    fn join(host: &Host) -> Connection {
+        Connection::new(TcpStream::connect(host.listener.local_addr().unwrap()).unwrap()).unwrap()
+    }
+
+    // Runs the host a frame at a time until the player's been sent something
+    fn next_message(host: &mut Host, game: &mut Game, assets: &Assets, player: &mut Connection) -> Message {
+        for _ in 0..500 {
+            host.update(game, Pos::new(0f32, 0f32), assets);
+            if let Some(message) = player.receive().into_iter().find(|m| !matches!(m, Message::Players { .. })) {
+                return message;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("The host never said anything");
+    }
+
+    #[test]
+    fn checksum_is_fnv1a() {
+        assert_eq!(checksum(""), 0xcbf29ce484222325);
+        assert_eq!(checksum("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(checksum("foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn players_get_welcomed_and_their_clicks_count() {
+        let (mut game, assets) = room();
+        let mut host = Host::start("127.0.0.1:0", false).unwrap();
+        let mut player = join(&host);
+        match next_message(&mut host, &mut game, &assets, &mut player) {
+            Message::Welcome { id, level, state, .. } => {
+                assert_eq!((id, level.as_str()), (1, "room"));
+                assert_eq!(state, game.shared_state());
+            }
+            _ => panic!("Joining should start with a welcome"),
+        }
+
+        let mut click = FrameInput::empty(0);
+        click.mouse = Pos::new(30f32, 320f32);
+        click.pressed.push(MouseButton::Left);
+        player.send(&Message::Input { line: click.to_line(), state: 0 });
+        next_message(&mut host, &mut game, &assets, &mut player);
+        assert!(game.shared_state().contains("clicked:north_table"));
+    }
+
+    #[test]
+    fn chat_gets_cut_down_before_being_passed_on() {
+        let (mut game, assets) = room();
+        let mut host = Host::start("127.0.0.1:0", false).unwrap();
+        let mut talker = join(&host);
+        let mut listener = join(&host);
+        next_message(&mut host, &mut game, &assets, &mut talker);
+        next_message(&mut host, &mut game, &assets, &mut listener);
+
+        talker.send(&Message::Chat { from: 7, text: "x".repeat(MAX_LENGTH * 10) });
+        match next_message(&mut host, &mut game, &assets, &mut listener) {
+            Message::Chat { from, text } => assert_eq!((from, text.len()), (1, MAX_LENGTH)),
+            _ => panic!("The chat should have been passed on"),
+        }
+    }
+}
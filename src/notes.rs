@@ -0,0 +1,96 @@
+use crate::input::FrameInput;
+use crate::ui;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// How close a right click has to be to a line to rub it out
+
+const ERASE_DISTANCE: f32 = 10.0;
+
+// Where the typed note goes, top left under the view's own text
+
+const TEXT_AT: [f32; 2] = [20.0, 140.0];
+
+// What the player scribbled and typed, kept in the save
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Notes {
+    // Lines drawn with the mouse, each a run of points
+    pub strokes: Vec<Vec<[f32; 2]>>,
+    pub text: String,
+}
+
+// See-through paper over whatever's on screen, so notes can be held up
+// against a puzzle. Dragging draws, right clicking a line rubs it out, and
+// typing writes
+
+#[derive(Default)]
+pub struct ScratchPad {
+    pub notes: Notes,
+    drawing: bool,
+}
+
+impl ScratchPad {
+    pub fn new() -> Self {
+        ScratchPad::default()
+    }
+
+    pub fn update(&mut self, input: &FrameInput) {
+        let at = [input.mouse.x, input.mouse.y];
+        if input.click().is_some() {
+            self.notes.strokes.push(vec![at]);
+            self.drawing = true;
+        } else if self.drawing {
+            let stroke = self.notes.strokes.last_mut().unwrap();
+            if stroke.last() != Some(&at) {
+                stroke.push(at);
+            }
+        }
+        if input.released.contains(&MouseButton::Left) {
+            self.drawing = false;
+        }
+
+        if input.pressed.contains(&MouseButton::Right) {
+            self.drawing = false;
+            let mouse = vec2(at[0], at[1]);
+            self.notes.strokes.retain(|stroke| {
+                !stroke.iter().any(|[x, y]| vec2(*x, *y).distance(mouse) < ERASE_DISTANCE)
+            });
+        }
+
+        if input.key(KeyCode::Backspace) {
+            self.notes.text.pop();
+        }
+        if input.key(KeyCode::Enter) {
+            self.notes.text.push('\n');
+        }
+        for c in &input.chars {
+            if !c.is_control() {
+                self.notes.text.push(*c);
+            }
+        }
+    }
+
+    // Lets go of a line being drawn, for when the pad gets put away
+    pub fn cancel(&mut self) {
+        self.drawing = false;
+    }
+
+    pub fn draw(&self) {
+        draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(1.0, 0.95, 0.7, 0.3));
+        let ink = Color::new(0.1, 0.15, 0.6, 1f32);
+        for stroke in &self.notes.strokes {
+            if let [[x, y]] = stroke.as_slice() {
+                draw_circle(*x, *y, 2f32, ink);
+            }
+            for pair in stroke.windows(2) {
+                let ([x1, y1], [x2, y2]) = (pair[0], pair[1]);
+                draw_line(x1, y1, x2, y2, 4f32, ink);
+            }
+        }
+        for (i, line) in self.notes.text.split('\n').enumerate() {
+            draw_text(line, TEXT_AT[0], TEXT_AT[1] + i as f32 * 30.0, 30.0, ink);
+        }
+        ui::text("Drag to draw, right click to erase, type to write", 20.0, 590.0, 20.0, ink);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::animation::{Animator, ClipData};
+use crate::assets::Assets;
+use crate::input::FrameInput;
+use crate::ui;
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// How far from a slot a dragged piece can be let go and still land in it
+
+const SNAP_DISTANCE: f32 = 100.0;
+
+// Clicking the area swaps whatever is in the two slots
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Swap {
+    pub area: [f32; 4],
+    pub slots: [usize; 2],
+}
+
+// An arrangement that means something, pieces listed by slot
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Outcome {
+    pub arrangement: Vec<usize>,
+    pub word: String,
+    #[serde(default)]
+    pub flag: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct PermutationData {
+    pieces: Vec<String>,
+    // Clips by piece texture, looping the whole time, like candles flickering
+    #[serde(default)]
+    animations: HashMap<String, ClipData>,
+    slots: Vec<[f32; 2]>,
+    start: Vec<usize>,
+    // Pieces can only trade places with their neighbours
+    #[serde(default)]
+    adjacent_only: bool,
+    #[serde(default)]
+    swaps: Vec<Swap>,
+    outcomes: Vec<Outcome>,
+    fallback: String,
+    word_at: [f32; 2],
+}
+
+// Pieces sitting in slots that get put in order by dragging them around,
+// with some orders spelling out a word
+
+#[derive(Clone)]
+pub struct PermutationPuzzle {
+    data: PermutationData,
+    pieces: Vec<Texture2D>,
+    animators: Vec<Option<Animator>>,
+    pub order: Vec<usize>,
+    // Slot being dragged out of, and where in the piece it was grabbed
+    dragging: Option<(usize, Vec2)>,
+    mouse: Vec2,
+}
+
+impl PermutationPuzzle {
+    pub fn new(data: &str, assets: &Assets) -> Result<Self, String> {
+        let data: PermutationData = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        Ok(PermutationPuzzle {
+            pieces: data.pieces.iter().map(|name| assets.texture(name)).collect::<Result<_, _>>()?,
+            animators: data
+                .pieces
+                .iter()
+                .map(|name| data.animations.get(name).map(|clip| Animator::looping(clip, assets)).transpose())
+                .collect::<Result<_, _>>()?,
+            order: data.start.clone(),
+            data,
+            dragging: None,
+            mouse: vec2(0f32, 0f32),
+        })
+    }
+
+    // A saved order, as long as it puts every piece in exactly one slot
+    pub fn restore(&mut self, order: Vec<usize>) -> Result<(), String> {
+        let mut pieces = order.clone();
+        pieces.sort();
+        pieces.dedup();
+        if order.len() != self.data.slots.len() || pieces.len() != order.len() {
+            return Err(format!("{:?} isn't an order of {} pieces", order, self.data.slots.len()));
+        }
+        if pieces.last().is_some_and(|piece| *piece >= self.pieces.len()) {
+            return Err(format!("{:?} has pieces the puzzle doesn't", order));
+        }
+        self.order = order;
+        self.dragging = None;
+        Ok(())
+    }
+
+    fn slot_area(&self, slot: usize) -> Rect {
+        let [x, y] = self.data.slots[slot];
+        let piece = self.pieces[self.order[slot]];
+        Rect::new(x, y, piece.width(), piece.height())
+    }
+
+    fn nearest_slot(&self, at: Vec2) -> Option<usize> {
+        (0..self.data.slots.len())
+            .map(|slot| (slot, self.slot_area(slot).center().distance(at)))
+            .filter(|(_, distance)| *distance < SNAP_DISTANCE)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(slot, _)| slot)
+    }
+
+    // Dropping a piece on a neighbour trades the two, otherwise it's pulled
+    // out and everything between shuffles over to make room
+    fn place(&mut self, from: usize, to: usize) -> bool {
+        if from == to {
+            return false;
+        }
+        if self.data.adjacent_only {
+            if from.abs_diff(to) != 1 {
+                return false;
+            }
+            self.order.swap(from, to);
+        } else {
+            let piece = self.order.remove(from);
+            self.order.insert(to, piece);
+        }
+        true
+    }
+
+    // Returns whether the order changed
+    pub fn update(&mut self, input: &FrameInput) -> bool {
+        self.mouse = vec2(input.mouse.x, input.mouse.y);
+        for animator in self.animators.iter_mut().flatten() {
+            animator.update(input.dt);
+        }
+
+        if let Some((from, offset)) = self.dragging {
+            if input.released.contains(&MouseButton::Left) || !input.down.contains(&MouseButton::Left) {
+                self.dragging = None;
+                let center = self.mouse - offset + self.slot_area(from).size() / 2.0;
+                return match self.nearest_slot(center) {
+                    Some(to) => self.place(from, to),
+                    None => false,
+                };
+            }
+            return false;
+        }
+
+        if input.click().is_none() {
+            return false;
+        }
+        if let Some(slot) = (0..self.order.len()).find(|slot| self.slot_area(*slot).contains(self.mouse)) {
+            self.dragging = Some((slot, self.mouse - self.slot_area(slot).point()));
+            return false;
+        }
+        for swap in &self.data.swaps {
+            let [x, y, w, h] = swap.area;
+            if Rect::new(x, y, w, h).contains(self.mouse) {
+                let [a, b] = swap.slots;
+                self.order.swap(a, b);
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.data.outcomes.iter().find(|o| o.arrangement == self.order)
+    }
+
+    fn draw_piece(&self, piece: usize, at: Vec2) {
+        match &self.animators[piece] {
+            Some(animator) => animator.draw(self.pieces[piece], at),
+            None => draw_texture(self.pieces[piece], at.x, at.y, WHITE),
+        }
+    }
+
+    pub fn draw(&self) {
+        for (slot, piece) in self.order.iter().enumerate() {
+            if matches!(self.dragging, Some((from, _)) if from == slot) {
+                continue;
+            }
+            let [x, y] = self.data.slots[slot];
+            self.draw_piece(*piece, vec2(x, y));
+        }
+
+        // The piece being dragged goes on top of everything else
+
+        if let Some((from, offset)) = self.dragging {
+            let at = self.mouse - offset;
+            self.draw_piece(self.order[from], at);
+        }
+
+        let word = match self.outcome() {
+            Some(outcome) => &outcome.word,
+            None => &self.data.fallback,
+        };
+        let [x, y] = self.data.word_at;
+        draw_text(&ui::tr(word), x, y, 50.0, YELLOW);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pos;
+
+    fn candles() -> PermutationPuzzle {
+        let mut assets = Assets::load_headless();
+        assets.load_level_headless("room").unwrap();
+        PermutationPuzzle::new(assets.data("candlecase_big"), &assets).unwrap()
+    }
+
+    fn press(x: f32, y: f32) -> FrameInput {
+        let mut input = FrameInput::empty(0);
+        input.mouse = Pos::new(x, y);
+        input.pressed.push(MouseButton::Left);
+        input.down.push(MouseButton::Left);
+        input
+    }
+
+    fn release(x: f32, y: f32) -> FrameInput {
+        let mut input = FrameInput::empty(0);
+        input.mouse = Pos::new(x, y);
+        input.released.push(MouseButton::Left);
+        input
+    }
+
+    #[test]
+    fn swap_buttons_trade_their_slots() {
+        let mut candles = candles();
+        assert!(candles.update(&press(180f32, 210f32)));
+        assert_eq!(candles.order, vec![2, 3, 1, 0]);
+    }
+
+    #[test]
+    fn pieces_only_drop_next_to_where_they_were() {
+        let mut candles = candles();
+        assert!(!candles.update(&press(130f32, 150f32)));
+        assert!(candles.update(&release(250f32, 150f32)));
+        assert_eq!(candles.order, vec![2, 3, 1, 0]);
+
+        candles.update(&press(130f32, 150f32));
+        assert!(!candles.update(&release(370f32, 150f32)));
+        assert_eq!(candles.order, vec![2, 3, 1, 0]);
+    }
+
+    #[test]
+    fn orders_spell_out_words() {
+        let mut candles = candles();
+        assert!(candles.outcome().is_none());
+        candles.restore(vec![2, 0, 3, 1]).unwrap();
+        assert_eq!(candles.outcome().map(|o| o.word.as_str()), Some("BEAVER"));
+    }
+
+    #[test]
+    fn restore_turns_away_orders_that_lose_pieces() {
+        let mut candles = candles();
+        assert!(candles.restore(vec![0, 0, 1, 2]).is_err());
+        assert!(candles.restore(vec![0, 1, 2, 4]).is_err());
+        assert!(candles.restore(vec![0, 1, 2]).is_err());
+        assert_eq!(candles.order, vec![3, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::input::FrameInput;
+use crate::ui;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// How many dialed numbers the call log holds on to
+
+const LOG_LENGTH: usize = 5;
+
+// Someone on the other end, what they say when called and anything that
+// calling them sets off
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Contact {
+    pub number: String,
+    pub response: String,
+    #[serde(default)]
+    pub sound: Option<String>,
+    #[serde(default)]
+    pub flag: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct Key {
+    digit: char,
+    area: [f32; 4],
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Directory {
+    pub max_length: usize,
+    // Digits per group when a number is shown
+    pub group: usize,
+    pub wrong_number: String,
+    keys: Vec<Key>,
+    call: [f32; 4],
+    clear: [f32; 4],
+    screen_at: [f32; 2],
+    log_at: [f32; 2],
+    pub contacts: Vec<Contact>,
+}
+
+// What dialing a number led to
+
+pub enum Call {
+    Answered(Contact),
+    WrongNumber(String),
+}
+
+// The number being dialed is kept apart from whatever the phone is showing,
+// so a response never ends up being dialed into
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct PhoneState {
+    pub input: String,
+    pub display: Option<String>,
+    pub log: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct Phone {
+    directory: Directory,
+    pub state: PhoneState,
+}
+
+fn area([x, y, w, h]: [f32; 4]) -> Rect {
+    Rect::new(x, y, w, h)
+}
+
+impl Phone {
+    pub fn new(data: &str) -> Result<Self, String> {
+        Ok(Phone {
+            directory: serde_json::from_str(data).map_err(|e| e.to_string())?,
+            state: PhoneState::default(),
+        })
+    }
+
+    // A saved phone, as long as what's being dialed fits on it
+    pub fn restore(&mut self, state: PhoneState) -> Result<(), String> {
+        if state.input.chars().count() > self.directory.max_length {
+            return Err(format!("{} is longer than a number can be", state.input));
+        }
+        self.state = state;
+        Ok(())
+    }
+
+    fn press(&mut self, digit: char) {
+        self.state.display = None;
+        if self.state.input.len() < self.directory.max_length {
+            self.state.input.push(digit);
+        }
+    }
+    fn backspace(&mut self) {
+        self.state.display = None;
+        self.state.input.pop();
+    }
+    fn clear(&mut self) {
+        self.state.display = None;
+        self.state.input.clear();
+    }
+
+    // Calling with nothing dialed redials the last number
+    fn call(&mut self) -> Option<Call> {
+        let state = &mut self.state;
+        let number = match state.input.is_empty() {
+            true => state.log.first()?.clone(),
+            false => std::mem::take(&mut state.input),
+        };
+        state.log.retain(|n| *n != number);
+        state.log.insert(0, number.clone());
+        state.log.truncate(LOG_LENGTH);
+
+        match self.directory.contacts.iter().find(|c| c.number == number) {
+            Some(contact) => {
+                state.display = Some(contact.response.clone());
+                Some(Call::Answered(contact.clone()))
+            }
+            None => {
+                state.display = Some(self.directory.wrong_number.clone());
+                Some(Call::WrongNumber(number))
+            }
+        }
+    }
+
+    // Digits can be clicked or typed, Backspace or right clicking the clear
+    // button takes one back
+    pub fn update(&mut self, input: &FrameInput) -> Option<Call> {
+        for c in &input.chars {
+            if c.is_ascii_digit() {
+                self.press(*c);
+            }
+        }
+        if input.key(KeyCode::Backspace) {
+            self.backspace();
+        }
+        let mut call = input.key(KeyCode::Enter);
+
+        let m = vec2(input.mouse.x, input.mouse.y);
+        if input.pressed.contains(&MouseButton::Right) && area(self.directory.clear).contains(m) {
+            self.backspace();
+        }
+        if input.click().is_some() {
+            let key = self.directory.keys.iter().find(|key| area(key.area).contains(m));
+            if let Some(digit) = key.map(|key| key.digit) {
+                self.press(digit);
+            } else if area(self.directory.call).contains(m) {
+                call = true;
+            } else if area(self.directory.clear).contains(m) {
+                self.clear();
+            }
+        }
+
+        match call {
+            true => self.call(),
+            false => None,
+        }
+    }
+
+    // Digits split into groups, e.g. 150405040720 as 1504 0504 0720
+    pub fn format(&self, number: &str) -> String {
+        let digits: Vec<char> = number.chars().collect();
+        let groups: Vec<String> = digits
+            .chunks(self.directory.group.max(1))
+            .map(|group| group.iter().collect())
+            .collect();
+        groups.join(" ")
+    }
+
+    // Whatever belongs on the phone's screen right now
+    fn screen(&self) -> String {
+        match &self.state.display {
+            Some(text) => text.clone(),
+            None => self.format(&self.state.input),
+        }
+    }
+
+    pub fn draw(&self) {
+        let [x, y] = self.directory.screen_at;
+        draw_text(&ui::tr(&self.screen()), x, y, 50.0, WHITE);
+
+        // Recently dialed numbers, newest first
+
+        let [x, y] = self.directory.log_at;
+        if !self.state.log.is_empty() {
+            ui::text("Recent calls", x, y, 30.0, WHITE);
+        }
+        for (i, number) in self.state.log.iter().enumerate() {
+            draw_text(&self.format(number), x, y + 40.0 + (i as f32 * 35.0), 30.0, LIGHTGRAY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phone() -> Phone {
+        Phone::new(&std::fs::read_to_string("assets/levels/room/phone_entry.json").unwrap()).unwrap()
+    }
+
+    fn typed(text: &str, enter: bool) -> FrameInput {
+        let mut input = FrameInput::empty(0);
+        input.chars = text.chars().collect();
+        if enter {
+            input.keys.push(KeyCode::Enter);
+        }
+        input
+    }
+
+    #[test]
+    fn calls_reach_the_right_contact() {
+        let mut phone = phone();
+        assert!(phone.update(&typed("12", false)).is_none());
+        match phone.update(&typed("34", true)) {
+            Some(Call::Answered(contact)) => assert_eq!(contact.response, "pumpkin"),
+            _ => panic!("1234 should be answered"),
+        }
+        assert_eq!(phone.screen(), "pumpkin");
+        assert!(matches!(phone.update(&typed("5555", true)), Some(Call::WrongNumber(n)) if n == "5555"));
+        assert_eq!(phone.state.log, vec!["5555", "1234"]);
+    }
+
+    #[test]
+    fn dialing_stops_at_the_longest_number() {
+        let mut phone = phone();
+        phone.update(&typed("15040504072099", false));
+        assert_eq!(phone.screen(), "1504 0504 0720");
+    }
+
+    #[test]
+    fn redials_with_nothing_dialed() {
+        let mut phone = phone();
+        phone.update(&typed("8659", true));
+        assert!(matches!(phone.update(&typed("", true)), Some(Call::Answered(c)) if c.number == "8659"));
+        assert_eq!(phone.state.log, vec!["8659"]);
+    }
+
+    #[test]
+    fn restore_turns_away_numbers_too_long_to_dial() {
+        let mut phone = phone();
+        let state = PhoneState { input: "1".repeat(13), ..Default::default() };
+        assert!(phone.restore(state).is_err());
+        assert!(phone.state.input.is_empty());
+    }
+
+    #[test]
+    fn bad_data_is_an_error() {
+        assert!(Phone::new("{}").is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::assets::Assets;
+use crate::clock::ClockPuzzle;
+use crate::input::FrameInput;
+use crate::journal::Clue;
+use crate::lock::{CombinationLock, LockResult};
+use crate::permutation::PermutationPuzzle;
+use crate::phone::{Call, Phone};
+use crate::wires::WirePuzzle;
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PuzzleKind {
+    Lock,
+    Phone,
+    Wires,
+    Permutation,
+    Clock,
+}
+
+// Something the game needs to act on after a puzzle handles input
+
+pub enum Outcome {
+    // Solved, with anything to say about it
+    Solved(Option<String>),
+    Wrong(String),
+    Flag(String),
+    Sound(String),
+    // Worth writing in the journal
+    Clue(Clue),
+}
+
+// Whatever puzzle a detail view holds, set up from that view's data file
+
+#[derive(Clone)]
+pub enum Puzzle {
+    Lock(CombinationLock),
+    Phone(Phone),
+    Wires(WirePuzzle),
+    Permutation(PermutationPuzzle),
+    Clock(ClockPuzzle),
+}
+
+impl Puzzle {
+    pub fn new(kind: PuzzleKind, data: &str, assets: &Assets) -> Result<Self, String> {
+        Ok(match kind {
+            PuzzleKind::Lock => Puzzle::Lock(CombinationLock::from_data(data, assets)?),
+            PuzzleKind::Phone => Puzzle::Phone(Phone::new(data)?),
+            PuzzleKind::Wires => Puzzle::Wires(WirePuzzle::new(data)?),
+            PuzzleKind::Permutation => Puzzle::Permutation(PermutationPuzzle::new(data, assets)?),
+            PuzzleKind::Clock => Puzzle::Clock(ClockPuzzle::new(data)?),
+        })
+    }
+
+    pub fn update(&mut self, input: &FrameInput) -> Vec<Outcome> {
+        let mut outcomes = Vec::new();
+        match self {
+            Puzzle::Lock(lock) => match lock.update(input) {
+                Some(LockResult::Correct) => outcomes.push(Outcome::Solved(lock.solved_text.clone())),
+                Some(LockResult::Wrong) => outcomes.push(Outcome::Wrong(lock.entry_string())),
+                None => (),
+            },
+            Puzzle::Phone(phone) => match phone.update(input) {
+                Some(Call::Answered(contact)) => {
+                    let number = phone.format(&contact.number);
+                    outcomes.push(Outcome::Clue(Clue::Call { number, response: contact.response.clone() }));
+                    outcomes.extend(contact.sound.map(Outcome::Sound));
+                    outcomes.extend(contact.flag.map(Outcome::Flag));
+                }
+                Some(Call::WrongNumber(number)) => outcomes.push(Outcome::Wrong(number)),
+                None => (),
+            },
+            Puzzle::Wires(wires) => {
+                if wires.update(input) {
+                    outcomes.extend(wires.readout().map(|r| Outcome::Clue(Clue::Text(r.text.clone()))));
+                    outcomes.extend(wires.readout().and_then(|r| r.flag.clone()).map(Outcome::Flag));
+                }
+            }
+            Puzzle::Permutation(permutation) => {
+                if permutation.update(input) {
+                    outcomes.extend(permutation.outcome().map(|o| Outcome::Clue(Clue::Text(o.word.clone()))));
+                    outcomes.extend(permutation.outcome().and_then(|o| o.flag.clone()).map(Outcome::Flag));
+                }
+            }
+            Puzzle::Clock(clock) => {
+                if clock.update(input) {
+                    // Whatever the clock does once set is likely a clue
+                    outcomes.extend(clock.solved_text().map(|text| Outcome::Clue(Clue::Text(text))));
+                    outcomes.push(Outcome::Solved(clock.solved_text()));
+                }
+            }
+        }
+        outcomes
+    }
+
+    // Keeps puzzles that show the time up to date, even when not on screen
+    pub fn tick(&mut self, elapsed: f32) {
+        if let Puzzle::Clock(clock) = self {
+            clock.tick(elapsed);
+        }
+    }
+
+    pub fn draw(&self, colorblind: bool) {
+        match self {
+            Puzzle::Lock(lock) => lock.draw(),
+            Puzzle::Phone(phone) => phone.draw(),
+            Puzzle::Wires(wires) => wires.draw(colorblind),
+            Puzzle::Permutation(permutation) => permutation.draw(),
+            Puzzle::Clock(clock) => clock.draw(),
+        }
+    }
+
+    // Drops anything half done, like a wire being dragged
+    pub fn cancel(&mut self) {
+        match self {
+            Puzzle::Wires(wires) => wires.cancel(),
+            Puzzle::Clock(clock) => clock.cancel(),
+            _ => (),
+        }
+    }
+
+    // Takes typing, so the keyboard shouldn't be used for anything else
+    pub fn typing(&self) -> bool {
+        matches!(self, Puzzle::Phone(_))
+    }
+
+    // Where the player has got to, for the save file
+
+    pub fn save(&self) -> Value {
+        let saved = match self {
+            Puzzle::Lock(lock) => serde_json::to_value(&lock.entry),
+            Puzzle::Phone(phone) => serde_json::to_value(&phone.state),
+            Puzzle::Wires(wires) => serde_json::to_value(&wires.wires),
+            Puzzle::Permutation(permutation) => serde_json::to_value(&permutation.order),
+            Puzzle::Clock(clock) => serde_json::to_value(clock.time),
+        };
+        saved.unwrap_or(Value::Null)
+    }
+    // Saves get edited and scripts make mistakes, so anything that doesn't
+    // fit the puzzle is turned away rather than left to crash it later
+    pub fn restore(&mut self, saved: Value) -> Result<(), String> {
+        let parse = |e: serde_json::Error| e.to_string();
+        match self {
+            Puzzle::Lock(lock) => lock.restore(serde_json::from_value(saved).map_err(parse)?),
+            Puzzle::Phone(phone) => phone.restore(serde_json::from_value(saved).map_err(parse)?),
+            Puzzle::Wires(wires) => wires.restore(serde_json::from_value(saved).map_err(parse)?),
+            Puzzle::Permutation(permutation) => permutation.restore(serde_json::from_value(saved).map_err(parse)?),
+            Puzzle::Clock(clock) => clock.restore(serde_json::from_value(saved).map_err(parse)?),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// How often to look for changed files, in seconds
+
+const POLL_INTERVAL: f32 = 0.5;
+
+// Keeps an eye on everything under assets/ in dev builds, by polling
+// modification times rather than anything platform specific
+
+pub struct Watcher {
+    modified: HashMap<PathBuf, SystemTime>,
+    timer: f32,
+}
+
+fn scan(dir: &Path, found: &mut HashMap<PathBuf, SystemTime>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            scan(&path, found);
+        } else if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+            found.insert(path, modified);
+        }
+    }
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        let mut modified = HashMap::new();
+        scan(Path::new("assets"), &mut modified);
+        Watcher { modified, timer: 0f32 }
+    }
+
+    // Files added or changed since last time, as paths like
+    // assets/levels/room.json
+    pub fn changed(&mut self, dt: f32) -> Vec<PathBuf> {
+        self.timer += dt;
+        if self.timer < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.timer = 0f32;
+
+        let mut now = HashMap::new();
+        scan(Path::new("assets"), &mut now);
+        let changed = now
+            .iter()
+            .filter(|(path, modified)| self.modified.get(*path) != Some(modified))
+            .map(|(path, _)| path.clone())
+            .collect();
+        self.modified = now;
+        changed
+    }
+}
@@ -0,0 +1,341 @@
+use crate::assets::Assets;
+use crate::game::Game;
+use crate::level;
+use crate::storage;
+use crate::telemetry::{load_log, Event, EventKind};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Default)]
+struct PuzzleStats {
+    sessions: usize,
+    visits: usize,
+    seconds: f32,
+    solve_times: Vec<f32>,
+    failures: usize,
+}
+
+#[derive(Default)]
+struct Summary {
+    sessions: usize,
+    completed: usize,
+    endings: BTreeMap<String, usize>,
+    lengths: Vec<f32>,
+    // Puzzles by level, then by tag, since levels can reuse a tag
+    puzzles: BTreeMap<String, BTreeMap<String, PuzzleStats>>,
+    // Clicks by level, then by view
+    clicks: BTreeMap<String, BTreeMap<String, Vec<(f32, f32)>>>,
+}
+
+impl Summary {
+    // Logs from before there were levels are all from the first one
+    fn add_session(&mut self, path: &str, first_level: &str) -> Result<(), String> {
+        self.add_events(load_log(path)?, first_level);
+        Ok(())
+    }
+
+    fn add_events(&mut self, events: Vec<Event>, first_level: &str) {
+        self.sessions += 1;
+        self.lengths.push(events.last().map(|e| e.t).unwrap_or(0f32));
+
+        if events.iter().any(|e| matches!(e.kind, EventKind::Ending { .. })) {
+            self.completed += 1;
+        }
+
+        let mut seen: Vec<(String, String)> = Vec::new();
+        let mut level = first_level.to_string();
+        for event in events {
+            match event.kind {
+                EventKind::LevelStart { level: started } => level = started,
+                EventKind::Click { view, x, y, .. } => {
+                    let views = self.clicks.entry(level.clone()).or_default();
+                    views.entry(view).or_default().push((x, y));
+                }
+                EventKind::PuzzleEnter { puzzle } => {
+                    let stats = self.puzzle(&level, &puzzle);
+                    stats.visits += 1;
+                    let key = (level.clone(), puzzle);
+                    if !seen.contains(&key) {
+                        stats.sessions += 1;
+                        seen.push(key);
+                    }
+                }
+                EventKind::PuzzleLeave { puzzle, seconds } => self.puzzle(&level, &puzzle).seconds += seconds,
+                EventKind::WrongAttempt { puzzle, .. } => self.puzzle(&level, &puzzle).failures += 1,
+                EventKind::PuzzleSolved { puzzle, seconds } => {
+                    self.puzzle(&level, &puzzle).solve_times.push(seconds);
+                }
+                EventKind::Ending { id } => *self.endings.entry(id).or_default() += 1,
+                EventKind::RoomChange { .. } => (),
+            }
+        }
+    }
+
+    fn puzzle(&mut self, level: &str, puzzle: &str) -> &mut PuzzleStats {
+        self.puzzles.entry(level.to_string()).or_default().entry(puzzle.to_string()).or_default()
+    }
+}
+
+fn median(values: &[f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    Some(sorted[sorted.len() / 2])
+}
+
+fn format_seconds(seconds: Option<f32>) -> String {
+    match seconds {
+        Some(s) => format!("{}:{:02}", (s / 60f32) as u32, (s % 60f32) as u32),
+        None => "-".to_string(),
+    }
+}
+
+// A level's layout, with the texture each item is drawn with
+
+struct Layout {
+    game: Game,
+    textures: HashMap<String, String>,
+}
+
+impl Layout {
+    fn load(assets: &mut Assets, level: &str) -> Result<Self, String> {
+        assets.load_level_headless(level)?;
+        let items = &assets.level().items;
+        let textures = items.iter().map(|item| (item.tag.clone(), item.texture.clone())).collect();
+        Ok(Layout { game: Game::new(assets)?, textures })
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// The view drawn with its textures from assets/, then item outlines and every
+// click as a faint dot on top, so busy spots build up into darker patches
+
+fn heatmap(layout: &Layout, view: &str, clicks: &[(f32, f32)]) -> String {
+    let mut svg = String::from(
+        "<svg viewBox=\"0 0 800 600\" width=\"800\" height=\"600\">\
+         <rect width=\"800\" height=\"600\" fill=\"#3f4857\"/>",
+    );
+    let items = layout.game.view_layout(view);
+    for (tag, bounds) in &items {
+        let Some(texture) = layout.textures.get(tag) else { continue };
+        svg += &format!(
+            "<image href=\"assets/{}.png\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+            escape(texture), bounds.top_left.x, bounds.top_left.y,
+            bounds.bottom_right.x - bounds.top_left.x, bounds.bottom_right.y - bounds.top_left.y,
+        );
+    }
+    for (tag, bounds) in &items {
+        let width = bounds.bottom_right.x - bounds.top_left.x;
+        let height = bounds.bottom_right.y - bounds.top_left.y;
+        svg += &format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#ccc\"/>\
+             <text x=\"{}\" y=\"{}\" fill=\"#ccc\" font-size=\"12\">{}</text>",
+            bounds.top_left.x, bounds.top_left.y, width, height,
+            bounds.top_left.x + 4f32, bounds.top_left.y + 14f32, escape(tag),
+        );
+    }
+    for (x, y) in clicks {
+        svg += &format!("<circle cx=\"{}\" cy=\"{}\" r=\"14\" fill=\"red\" fill-opacity=\"0.2\"/>", x, y);
+    }
+    svg + "</svg>"
+}
+
+fn render(summary: &Summary, layouts: &BTreeMap<String, Layout>) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Playtest report</title>\
+         <style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}\
+         td,th{border:1px solid #999;padding:4px 10px;text-align:right}\
+         th:first-child,td:first-child{text-align:left}</style></head><body>\n",
+    );
+
+    html += "<h1>Playtest report</h1>\n";
+    html += &format!(
+        "<p>{} sessions, {} reached an ending. Median session length {}.</p>\n",
+        summary.sessions, summary.completed, format_seconds(median(&summary.lengths)),
+    );
+    for (ending, count) in &summary.endings {
+        html += &format!("<p>Ending <b>{}</b> reached {} times.</p>\n", escape(ending), count);
+    }
+
+    html += "<h2>Puzzles</h2>\n";
+    for (level, puzzles) in &summary.puzzles {
+        html += &format!(
+            "<h3>{}</h3>\n<table><tr><th>Puzzle</th><th>Sessions</th><th>Visits</th>\
+             <th>Avg time spent</th><th>Solved</th><th>Median solve time</th>\
+             <th>Fastest solve</th><th>Wrong attempts</th><th>Wrong per session</th></tr>\n",
+            escape(level),
+        );
+        for (puzzle, stats) in puzzles {
+            let sessions = stats.sessions.max(1) as f32;
+            html += &format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                 <td>{}</td><td>{}</td><td>{:.1}</td></tr>\n",
+                escape(puzzle),
+                stats.sessions,
+                stats.visits,
+                format_seconds(Some(stats.seconds / sessions)),
+                stats.solve_times.len(),
+                format_seconds(median(&stats.solve_times)),
+                format_seconds(stats.solve_times.iter().copied().reduce(f32::min)),
+                stats.failures,
+                stats.failures as f32 / sessions,
+            );
+        }
+        html += "</table>\n";
+    }
+
+    for (level, views) in &summary.clicks {
+        let Some(layout) = layouts.get(level) else { continue };
+        html += &format!("<h2>Clicks in {}</h2>\n", escape(level));
+        // Rooms get their heatmaps first, in the order the level lists them
+        let rooms = layout.game.view_names();
+        let mut tags: Vec<&String> = views.keys().collect();
+        tags.sort_by_key(|view| rooms.iter().position(|room| room == *view).unwrap_or(rooms.len()));
+        for view in tags {
+            let clicks = &views[view];
+            html += &format!("<h3>{} ({} clicks)</h3>\n", escape(view), clicks.len());
+            html += &heatmap(layout, view, clicks);
+            html += "\n";
+        }
+    }
+
+    html + "</body></html>\n"
+}
+
+// Rolls any number of session logs up into a single HTML page
+
+pub fn run(logs: &[String], out: &str) -> Result<(), String> {
+    if logs.is_empty() {
+        return Err("No telemetry logs given to report on".to_string());
+    }
+    storage::disable_writes();
+    let mut assets = Assets::load_headless();
+    let campaign = level::campaign(&assets)?;
+
+    let mut summary = Summary::default();
+    for path in logs {
+        summary.add_session(path, &campaign[0].id)?;
+    }
+
+    // Each level clicked in gets loaded for its layout
+    let mut layouts = BTreeMap::new();
+    for level in summary.clicks.keys() {
+        if campaign.iter().any(|info| info.id == *level) {
+            layouts.insert(level.clone(), Layout::load(&mut assets, level)?);
+        }
+    }
+
+    std::fs::write(out, render(&summary, &layouts)).map_err(|e| format!("Couldn't write {}: {}", out, e))?;
+    println!("Wrote report on {} sessions to {}", summary.sessions, out);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(lines: &[&str]) -> Vec<Event> {
+        lines.iter().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn sessions_add_up() {
+        let mut summary = Summary::default();
+        summary.add_events(
+            session(&[
+                r#"{"t":1,"event":"puzzle_enter","puzzle":"door_pad"}"#,
+                r#"{"t":3,"event":"wrong_attempt","puzzle":"door_pad","entry":"1111"}"#,
+                r#"{"t":5,"event":"puzzle_solved","puzzle":"door_pad","seconds":4}"#,
+                r#"{"t":5,"event":"click","view":"North","x":200,"y":200,"tag":"exit_door"}"#,
+                r#"{"t":6,"event":"ending","id":"homebody"}"#,
+            ]),
+            "room",
+        );
+        summary.add_events(
+            session(&[
+                r#"{"t":1,"event":"puzzle_enter","puzzle":"door_pad"}"#,
+                r#"{"t":2,"event":"puzzle_leave","puzzle":"door_pad","seconds":1}"#,
+                r#"{"t":3,"event":"puzzle_enter","puzzle":"door_pad"}"#,
+                r#"{"t":4,"event":"click","view":"North","x":10,"y":20,"tag":null}"#,
+            ]),
+            "room",
+        );
+
+        assert_eq!((summary.sessions, summary.completed), (2, 1));
+        assert_eq!(summary.lengths, vec![6f32, 4f32]);
+        assert_eq!(summary.endings["homebody"], 1);
+
+        let door = &summary.puzzles["room"]["door_pad"];
+        assert_eq!((door.sessions, door.visits, door.failures), (2, 3, 1));
+        assert_eq!(door.seconds, 1f32);
+        assert_eq!(door.solve_times, vec![4f32]);
+
+        assert_eq!(summary.clicks["room"]["North"], vec![(200f32, 200f32), (10f32, 20f32)]);
+    }
+
+    #[test]
+    fn clicks_go_with_the_level_they_were_in() {
+        let mut summary = Summary::default();
+        summary.add_events(
+            session(&[
+                r#"{"t":1,"event":"click","view":"North","x":1,"y":1,"tag":null}"#,
+                r#"{"t":2,"event":"level_start","level":"annex"}"#,
+                r#"{"t":3,"event":"click","view":"North","x":2,"y":2,"tag":null}"#,
+            ]),
+            "room",
+        );
+        assert_eq!(summary.clicks["room"]["North"], vec![(1f32, 1f32)]);
+        assert_eq!(summary.clicks["annex"]["North"], vec![(2f32, 2f32)]);
+    }
+
+    #[test]
+    fn median_takes_the_middle() {
+        assert_eq!(median(&[]), None);
+        assert_eq!(median(&[9f32, 1f32, 5f32]), Some(5f32));
+        assert_eq!(format_seconds(Some(125f32)), "2:05");
+    }
+
+    #[test]
+    fn levels_keep_their_own_puzzles() {
+        let mut summary = Summary::default();
+        summary.add_events(
+            session(&[
+                r#"{"t":0,"event":"level_start","level":"room"}"#,
+                r#"{"t":1,"event":"puzzle_enter","puzzle":"door_pad"}"#,
+                r#"{"t":3,"event":"wrong_attempt","puzzle":"door_pad","entry":"1111"}"#,
+                r#"{"t":5,"event":"puzzle_solved","puzzle":"door_pad","seconds":4}"#,
+            ]),
+            "room",
+        );
+        summary.add_events(
+            session(&[
+                r#"{"t":0,"event":"level_start","level":"annex"}"#,
+                r#"{"t":1,"event":"puzzle_enter","puzzle":"door_pad"}"#,
+                r#"{"t":2,"event":"puzzle_leave","puzzle":"door_pad","seconds":1}"#,
+                r#"{"t":3,"event":"puzzle_enter","puzzle":"door_pad"}"#,
+            ]),
+            "room",
+        );
+
+        let room = &summary.puzzles["room"]["door_pad"];
+        assert_eq!((room.sessions, room.visits, room.failures), (1, 1, 1));
+        assert_eq!(room.solve_times, vec![4f32]);
+
+        let annex = &summary.puzzles["annex"]["door_pad"];
+        assert_eq!((annex.sessions, annex.visits, annex.failures), (1, 2, 0));
+        assert_eq!(annex.seconds, 1f32);
+        assert!(annex.solve_times.is_empty());
+    }
+
+    #[test]
+    fn heatmaps_draw_the_view_under_the_clicks() {
+        let layout = Layout::load(&mut Assets::load_headless(), "room").unwrap();
+        let svg = heatmap(&layout, "door_pad", &[(130f32, 40f32)]);
+        let image = svg.find("<image href=\"assets/ExitDoorPad.png\" x=\"125\" y=\"25\"").unwrap();
+        assert!(image < svg.find("<circle").unwrap());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::flags::Flags;
+use rhai::{Array, Dynamic, Engine, AST};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Keeps a runaway loop in a level's script from hanging the game
+
+const MAX_OPERATIONS: u64 = 100_000;
+
+// Something a script asked for, applied to the game once it's done running
+
+pub enum Action {
+    SetFlag(String),
+    ClearFlag(String),
+    Say(Vec<String>),
+    Go(String),
+    Play(String),
+    Animate(String, String),
+    SetPuzzle(String, Value),
+}
+
+// What a script can see while it runs. Flags it changes show up straight
+// away, everything else it does waits in the action list
+
+#[derive(Default)]
+struct State {
+    flags: Flags,
+    puzzles: HashMap<String, Value>,
+    view: String,
+    actions: Vec<Action>,
+}
+
+// Item click handlers and puzzle solve actions, written in Rhai right in the
+// level data and compiled up front by a name like click:<tag> or solve:<tag>
+
+pub struct Scripts {
+    engine: Engine,
+    scripts: HashMap<String, AST>,
+    state: Rc<RefCell<State>>,
+}
+
+impl Scripts {
+    pub fn new() -> Self {
+        let state = Rc::new(RefCell::new(State::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        // Story flags, items are hidden with a hidden:<tag> flag so that
+        // gets saved along with everything else
+
+        let s = state.clone();
+        engine.register_fn("flag", move |name: &str| s.borrow().flags.contains(name));
+        let s = state.clone();
+        engine.register_fn("solved", move |tag: &str| s.borrow().flags.contains(&format!("solved:{}", tag)));
+        let s = state.clone();
+        engine.register_fn("set_flag", move |name: &str| set_flag(&s, name.to_string(), true));
+        let s = state.clone();
+        engine.register_fn("clear_flag", move |name: &str| set_flag(&s, name.to_string(), false));
+        let s = state.clone();
+        engine.register_fn("show", move |tag: &str| set_flag(&s, format!("hidden:{}", tag), false));
+        let s = state.clone();
+        engine.register_fn("hide", move |tag: &str| set_flag(&s, format!("hidden:{}", tag), true));
+
+        // Text, views, sounds and animations
+
+        let s = state.clone();
+        engine.register_fn("say", move |text: &str| {
+            s.borrow_mut().actions.push(Action::Say(vec![text.to_string()]));
+        });
+        let s = state.clone();
+        engine.register_fn("say", move |lines: Array| {
+            let lines = lines.iter().map(|line| line.to_string()).collect();
+            s.borrow_mut().actions.push(Action::Say(lines));
+        });
+        let s = state.clone();
+        engine.register_fn("view", move || s.borrow().view.clone());
+        let s = state.clone();
+        engine.register_fn("go", move |view: &str| s.borrow_mut().actions.push(Action::Go(view.to_string())));
+        let s = state.clone();
+        engine.register_fn("play", move |sound: &str| s.borrow_mut().actions.push(Action::Play(sound.to_string())));
+        let s = state.clone();
+        engine.register_fn("animate", move |tag: &str, clip: &str| {
+            s.borrow_mut().actions.push(Action::Animate(tag.to_string(), clip.to_string()));
+        });
+
+        // Puzzle state, in the same shape it takes in the save file
+
+        let s = state.clone();
+        engine.register_fn("puzzle", move |tag: &str| {
+            let state = s.borrow();
+            let saved = state.puzzles.get(tag).cloned().unwrap_or(Value::Null);
+            rhai::serde::to_dynamic(saved).unwrap_or(Dynamic::UNIT)
+        });
+        let s = state.clone();
+        engine.register_fn("set_puzzle", move |tag: &str, value: Dynamic| {
+            match rhai::serde::from_dynamic::<Value>(&value) {
+                Ok(value) => s.borrow_mut().actions.push(Action::SetPuzzle(tag.to_string(), value)),
+                Err(e) => eprintln!("Script gave {} a state it can't take: {}", tag, e),
+            }
+        });
+
+        Scripts { engine, scripts: HashMap::new(), state }
+    }
+
+    // A script that doesn't compile is a mistake in the level data
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let ast = self.engine.compile(source).map_err(|e| format!("Script {} doesn't compile: {}", name, e))?;
+        self.scripts.insert(name.to_string(), ast);
+        Ok(())
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.scripts.contains_key(name)
+    }
+
+    // Runs a script against a copy of the game's flags and puzzles, handing
+    // back everything it asked for
+    pub fn run(
+        &self,
+        name: &str,
+        flags: &Flags,
+        puzzles: HashMap<String, Value>,
+        view: &str,
+    ) -> Vec<Action> {
+        let Some(ast) = self.scripts.get(name) else { return Vec::new() };
+        *self.state.borrow_mut() = State {
+            flags: flags.clone(),
+            puzzles,
+            view: view.to_string(),
+            actions: Vec::new(),
+        };
+        if let Err(e) = self.engine.run_ast(ast) {
+            eprintln!("Script {} failed: {}", name, e);
+        }
+        std::mem::take(&mut self.state.borrow_mut().actions)
+    }
+}
+
+fn set_flag(state: &Rc<RefCell<State>>, flag: String, on: bool) {
+    let mut state = state.borrow_mut();
+    match on {
+        true => state.flags.insert(flag.clone()),
+        false => state.flags.remove(&flag),
+    };
+    state.actions.push(match on {
+        true => Action::SetFlag(flag),
+        false => Action::ClearFlag(flag),
+    });
+}
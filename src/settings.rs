@@ -0,0 +1,76 @@
+use crate::assets::Assets;
+use crate::storage;
+use crate::ui;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Languages with a translation in assets/lang/, English is the source text
+
+pub const LANGUAGES: &[(&str, &str)] = &[("en", "English"), ("es", "Español")];
+
+pub const TEXT_SIZES: &[f32] = &[0.8, 1.0, 1.25, 1.5];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub volume: f32,
+    pub fullscreen: bool,
+    pub text_size: f32,
+    pub language: String,
+    pub colorblind: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            volume: 0.8,
+            fullscreen: false,
+            text_size: 1.0,
+            language: "en".to_string(),
+            colorblind: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        storage::load("settings")
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+    pub fn save(&self) {
+        if let Ok(text) = serde_json::to_string_pretty(self) {
+            storage::save("settings", &text);
+        }
+    }
+    pub fn apply(&self, assets: &Assets) {
+        set_fullscreen(self.fullscreen);
+        ui::set_text_scale(self.text_size);
+        let translations: HashMap<String, String> = match self.language.as_str() {
+            "en" => HashMap::new(),
+            language => serde_json::from_str(assets.data(&format!("lang/{}", language))).unwrap_or_default(),
+        };
+        ui::set_translations(translations);
+    }
+    pub fn language_name(&self) -> &'static str {
+        LANGUAGES
+            .iter()
+            .find(|(code, _)| *code == self.language)
+            .map(|(_, name)| *name)
+            .unwrap_or("English")
+    }
+    pub fn next_language(&mut self) {
+        let index = LANGUAGES.iter().position(|(code, _)| *code == self.language).unwrap_or(0);
+        self.language = LANGUAGES[(index + 1) % LANGUAGES.len()].0.to_string();
+    }
+    pub fn next_text_size(&mut self) {
+        let index = TEXT_SIZES.iter().position(|s| *s == self.text_size).unwrap_or(0);
+        self.text_size = TEXT_SIZES[(index + 1) % TEXT_SIZES.len()];
+    }
+    // Steps of 10%, wrapping back round to silent
+    pub fn next_volume(&mut self) {
+        let step = (self.volume * 10f32).round() as i32;
+        self.volume = ((step + 1) % 11) as f32 / 10f32;
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Small pieces of state that outlive a run, stored as files under save/, or
+// in the browser's localStorage under the same names on the web
+
+const SAVE_DIR: &str = "save";
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+// Replays and reports read what's there but must never write over it
+
+pub fn disable_writes() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+pub fn enable_writes() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn load(key: &str) -> Option<String> {
+    backend::load(&format!("{}/{}", SAVE_DIR, key))
+}
+
+pub fn save(key: &str, value: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    backend::save(&format!("{}/{}", SAVE_DIR, key), value);
+}
+
+pub fn remove(key: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    backend::remove(&format!("{}/{}", SAVE_DIR, key));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    pub fn load(path: &str) -> Option<String> {
+        std::fs::read_to_string(format!("{}.json", path)).ok()
+    }
+
+    pub fn save(path: &str, value: &str) {
+        let _ = std::fs::create_dir_all(super::SAVE_DIR);
+        if let Err(e) = std::fs::write(format!("{}.json", path), value) {
+            eprintln!("Couldn't save {}: {}", path, e);
+        }
+    }
+
+    pub fn remove(path: &str) {
+        let _ = std::fs::remove_file(format!("{}.json", path));
+    }
+}
+
+// Implemented in web/storage.js. Strings cross over as pointers into wasm
+// memory, and reading a value takes two calls so this side can make room
+// for it first
+
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    extern "C" {
+        fn storage_length(key: *const u8, key_len: usize) -> i32;
+        fn storage_read(key: *const u8, key_len: usize, out: *mut u8);
+        fn storage_write(key: *const u8, key_len: usize, value: *const u8, value_len: usize);
+        fn storage_remove(key: *const u8, key_len: usize);
+    }
+
+    // Lets the JS side check it was built for this code
+    #[no_mangle]
+    pub extern "C" fn storage_crate_version() -> u32 {
+        1 << 16
+    }
+
+    pub fn load(key: &str) -> Option<String> {
+        let length = unsafe { storage_length(key.as_ptr(), key.len()) };
+        if length < 0 {
+            return None;
+        }
+        let mut bytes = vec![0u8; length as usize];
+        unsafe { storage_read(key.as_ptr(), key.len(), bytes.as_mut_ptr()) };
+        String::from_utf8(bytes).ok()
+    }
+
+    pub fn save(key: &str, value: &str) {
+        unsafe { storage_write(key.as_ptr(), key.len(), value.as_ptr(), value.len()) };
+    }
+
+    pub fn remove(key: &str) {
+        unsafe { storage_remove(key.as_ptr(), key.len()) };
+    }
+}
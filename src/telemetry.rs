@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{LineWriter, Write};
+
+// Something worth knowing about from a playtest, t is seconds into the session
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Event {
+    pub t: f32,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    LevelStart { level: String },
+    RoomChange { room: String },
+    Click { view: String, x: f32, y: f32, tag: Option<String> },
+    PuzzleEnter { puzzle: String },
+    PuzzleLeave { puzzle: String, seconds: f32 },
+    WrongAttempt { puzzle: String, entry: String },
+    PuzzleSolved { puzzle: String, seconds: f32 },
+    Ending { id: String },
+}
+
+// Writes events as JSON lines, one file per session
+
+pub struct Telemetry {
+    writer: Option<LineWriter<File>>,
+}
+
+impl Telemetry {
+    pub fn new(path: Option<&str>) -> Self {
+        let writer = path.and_then(|path| {
+            if let Some(dir) = std::path::Path::new(path).parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            match File::create(path) {
+                Ok(file) => Some(LineWriter::new(file)),
+                Err(e) => {
+                    eprintln!("Couldn't log telemetry to {}: {}", path, e);
+                    None
+                }
+            }
+        });
+        Telemetry { writer }
+    }
+    pub fn write(&mut self, events: Vec<Event>) {
+        let Some(writer) = &mut self.writer else { return };
+        for event in events {
+            if let Ok(line) = serde_json::to_string(&event) {
+                let _ = writeln!(writer, "{}", line);
+            }
+        }
+    }
+}
+
+pub fn load_log(path: &str) -> Result<Vec<Event>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path, e))?;
+    let mut events = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str(line)
+            .map_err(|e| format!("{}:{}: {}", path, i + 1, e))?;
+        events.push(event);
+    }
+    Ok(events)
+}
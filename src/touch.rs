@@ -0,0 +1,230 @@
+use crate::input::FrameInput;
+use crate::Pos;
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+// How far a finger can drift and still count as a tap or long press
+
+const TAP_SLOP: f32 = 12.0;
+
+// A finger that moves straight away only turns into a drag after this long,
+// anything let go of sooner might be a swipe
+
+const SWIPE_TIME: f32 = 0.3;
+const SWIPE_DISTANCE: f32 = 100.0;
+
+// Holding still this long is the secondary action, like a right click
+
+const LONG_PRESS: f32 = 0.5;
+
+// How far outside a small target a finger can land and still hit it
+
+const TOUCH_REACH: f32 = 25.0;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Swipe {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy)]
+enum Phase {
+    // Down, but not yet a tap, drag, swipe or long press
+    Waiting { start: Vec2, time: f32 },
+    Dragging,
+    LongPress,
+}
+
+// Turns the first finger on the screen into the same input a mouse gives:
+// taps click, drags drag and long presses right click. Swipes come through
+// on their own
+
+#[derive(Default)]
+pub struct Gestures {
+    finger: Option<(u64, Phase)>,
+    // Sticks once the screen's been touched, until a mouse gets used
+    pub active: bool,
+}
+
+impl Gestures {
+    pub fn update(&mut self, input: &mut FrameInput) {
+        self.handle(&touches(), input);
+    }
+
+    fn handle(&mut self, touches: &[Touch], input: &mut FrameInput) {
+        if !input.pressed.is_empty() {
+            self.active = false;
+        }
+        let touch = match self.finger {
+            Some((id, _)) => touches.iter().find(|t| t.id == id),
+            None => touches.iter().find(|t| t.phase == TouchPhase::Started || t.phase == TouchPhase::Ended),
+        };
+        let Some(touch) = touch else {
+            self.finger = None;
+            return;
+        };
+        self.active = true;
+        let at = touch.position;
+        input.mouse = Pos::new(at.x, at.y);
+        let ended = matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled);
+
+        let (id, phase) = match self.finger {
+            Some(finger) => finger,
+            None => (touch.id, Phase::Waiting { start: at, time: 0f32 }),
+        };
+        let phase = match phase {
+            Phase::Waiting { start, time } => {
+                let time = time + input.dt;
+                let moved = start.distance(at) > TAP_SLOP;
+                let offset = at - start;
+                if ended && offset.x.abs() > SWIPE_DISTANCE && offset.x.abs() > offset.y.abs() * 2.0 {
+                    input.swipe = Some(if offset.x < 0f32 { Swipe::Left } else { Swipe::Right });
+                    Phase::Waiting { start, time }
+                } else if ended {
+                    // A quick tap, or a flick too small to be a swipe
+                    input.mouse = Pos::new(start.x, start.y);
+                    input.pressed.push(MouseButton::Left);
+                    input.released.push(MouseButton::Left);
+                    Phase::Waiting { start, time }
+                } else if moved && time >= SWIPE_TIME {
+                    // Picks things up from where the finger first went down
+                    input.mouse = Pos::new(start.x, start.y);
+                    input.pressed.push(MouseButton::Left);
+                    input.down.push(MouseButton::Left);
+                    Phase::Dragging
+                } else if !moved && time >= LONG_PRESS {
+                    input.pressed.push(MouseButton::Right);
+                    input.down.push(MouseButton::Right);
+                    Phase::LongPress
+                } else {
+                    Phase::Waiting { start, time }
+                }
+            }
+            Phase::Dragging => {
+                match ended {
+                    true => input.released.push(MouseButton::Left),
+                    false => input.down.push(MouseButton::Left),
+                }
+                Phase::Dragging
+            }
+            Phase::LongPress => {
+                match ended {
+                    true => input.released.push(MouseButton::Right),
+                    false => input.down.push(MouseButton::Right),
+                }
+                Phase::LongPress
+            }
+        };
+        self.finger = if ended { None } else { Some((id, phase)) };
+    }
+}
+
+fn distance(rect: Rect, at: Vec2) -> f32 {
+    let dx = (rect.x - at.x).max(at.x - (rect.x + rect.w)).max(0f32);
+    let dy = (rect.y - at.y).max(at.y - (rect.y + rect.h)).max(0f32);
+    vec2(dx, dy).length()
+}
+
+// Which of some targets a point is on. Fingers are less exact than a mouse,
+// so with touch the closest target in reach counts too
+
+pub fn target<T: Copy>(targets: impl IntoIterator<Item = (T, Rect)>, at: Vec2, touch: bool) -> Option<T> {
+    let targets: Vec<(T, Rect)> = targets.into_iter().collect();
+    if let Some((hit, _)) = targets.iter().find(|(_, rect)| rect.contains(at)) {
+        return Some(*hit);
+    }
+    if !touch {
+        return None;
+    }
+    targets
+        .iter()
+        .map(|(t, rect)| (*t, distance(*rect, at)))
+        .filter(|(_, d)| *d <= TOUCH_REACH)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(t, _)| t)
+}
+
+// Extra room for grabbing things that aren't rectangles, like clock hands
+pub fn reach(touch: bool) -> f32 {
+    if touch {
+        TOUCH_REACH
+    } else {
+        0f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Feeds one finger through a frame at a time, handing back each frame's input
+    fn gesture(steps: &[(TouchPhase, f32, f32)], dt: f32) -> Vec<FrameInput> {
+        let mut gestures = Gestures::default();
+        steps
+            .iter()
+            .map(|(phase, x, y)| {
+                let mut input = FrameInput::empty(0);
+                input.dt = dt;
+                gestures.handle(&[Touch { id: 3, phase: *phase, position: vec2(*x, *y) }], &mut input);
+                input
+            })
+            .collect()
+    }
+
+    #[test]
+    fn taps_click_where_they_started() {
+        let frames = gesture(&[(TouchPhase::Started, 100f32, 100f32), (TouchPhase::Ended, 105f32, 102f32)], 0.05);
+        assert!(frames[0].pressed.is_empty());
+        assert_eq!(frames[1].click(), Some(Pos::new(100f32, 100f32)));
+        assert_eq!(frames[1].released, vec![MouseButton::Left]);
+    }
+
+    #[test]
+    fn quick_flicks_swipe() {
+        let frames = gesture(&[(TouchPhase::Started, 400f32, 300f32), (TouchPhase::Ended, 250f32, 310f32)], 0.05);
+        assert_eq!(frames[1].swipe, Some(Swipe::Left));
+        assert!(frames[1].pressed.is_empty());
+    }
+
+    #[test]
+    fn slow_moves_drag() {
+        let frames = gesture(
+            &[
+                (TouchPhase::Started, 100f32, 100f32),
+                (TouchPhase::Moved, 150f32, 100f32),
+                (TouchPhase::Moved, 200f32, 100f32),
+                (TouchPhase::Ended, 200f32, 100f32),
+            ],
+            0.1,
+        );
+        assert!(frames[1].pressed.is_empty());
+        assert_eq!(frames[2].click(), Some(Pos::new(100f32, 100f32)));
+        assert_eq!(frames[3].released, vec![MouseButton::Left]);
+        assert_eq!(frames[3].mouse, Pos::new(200f32, 100f32));
+    }
+
+    #[test]
+    fn holding_still_right_clicks() {
+        let frames = gesture(
+            &[
+                (TouchPhase::Started, 100f32, 100f32),
+                (TouchPhase::Stationary, 101f32, 100f32),
+                (TouchPhase::Stationary, 101f32, 100f32),
+                (TouchPhase::Ended, 101f32, 100f32),
+            ],
+            0.3,
+        );
+        assert_eq!(frames[1].pressed, vec![MouseButton::Right]);
+        assert_eq!(frames[3].released, vec![MouseButton::Right]);
+    }
+
+    #[test]
+    fn fingers_reach_nearby_targets() {
+        let targets = [(1, Rect::new(0f32, 0f32, 10f32, 10f32)), (2, Rect::new(40f32, 0f32, 10f32, 10f32))];
+        assert_eq!(target(targets, vec2(25f32, 5f32), false), None);
+        assert_eq!(target(targets, vec2(25f32, 5f32), true), Some(1));
+        assert_eq!(target(targets, vec2(32f32, 5f32), true), Some(2));
+        assert_eq!(target(targets, vec2(100f32, 5f32), true), None);
+    }
+}
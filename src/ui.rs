@@ -0,0 +1,107 @@
+use crate::input::FrameInput;
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+// Player facing text goes through here so the language and text size
+// settings apply everywhere without passing them around
+
+thread_local! {
+    static TRANSLATIONS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+    static TEXT_SCALE: Cell<f32> = const { Cell::new(1f32) };
+}
+
+// Translations are keyed by the original English text, anything missing
+// just shows up in English
+
+pub fn set_translations(translations: HashMap<String, String>) {
+    TRANSLATIONS.with(|t| *t.borrow_mut() = translations);
+}
+
+pub fn set_text_scale(scale: f32) {
+    TEXT_SCALE.with(|s| s.set(scale));
+}
+
+pub fn text_scale() -> f32 {
+    TEXT_SCALE.with(|s| s.get())
+}
+
+pub fn tr(text: &str) -> String {
+    TRANSLATIONS.with(|t| t.borrow().get(text).cloned().unwrap_or_else(|| text.to_string()))
+}
+
+pub fn text(text: &str, x: f32, y: f32, size: f32, color: Color) {
+    draw_text(&tr(text), x, y, size * text_scale(), color);
+}
+
+// Vertical list of centered options, returns which one was clicked.
+// Disabled options are drawn but can't be picked
+
+pub fn menu(title: &str, options: &[(String, bool)], input: &FrameInput) -> Option<usize> {
+    let title = tr(title);
+    let size = measure_text(&title, None, 60, 1f32);
+    draw_text(&title, 400.0 - size.width / 2.0, 110.0, 60.0, YELLOW);
+
+    let mut picked = None;
+    for (i, (label, enabled)) in options.iter().enumerate() {
+        let y = 200.0 + (i as f32 * 60.0);
+        let hovered = input.mouse.y > y - 40.0 && input.mouse.y < y + 10.0
+            && input.mouse.x > 200.0 && input.mouse.x < 600.0;
+        if hovered && *enabled {
+            draw_rectangle(200.0, y - 40.0, 400.0, 50.0, Color::new(1f32, 1f32, 1f32, 0.15));
+            if input.click().is_some() {
+                picked = Some(i);
+            }
+        }
+        let label = tr(label);
+        let size = measure_text(&label, None, 40, 1f32);
+        let color = if *enabled { WHITE } else { DARKGRAY };
+        draw_text(&label, 400.0 - size.width / 2.0, y, 40.0, color);
+    }
+    picked
+}
+
+// Line broken into dashes of the given length and gap, no gap draws it solid
+
+pub fn dashed_line(from: Vec2, to: Vec2, thickness: f32, dash: f32, gap: f32, color: Color) {
+    let length = from.distance(to);
+    if gap <= 0f32 || length <= 0f32 {
+        draw_line(from.x, from.y, to.x, to.y, thickness, color);
+        return;
+    }
+    let direction = (to - from) / length;
+    let mut start = 0f32;
+    while start < length {
+        let end = (start + dash).min(length);
+        let a = from + direction * start;
+        let b = from + direction * end;
+        draw_line(a.x, a.y, b.x, b.y, thickness, color);
+        start = end + gap;
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    Circle,
+    Square,
+    Triangle,
+    Diamond,
+}
+
+// Small filled shape centered on a point, for telling things apart without color
+
+pub fn shape(shape: Shape, center: Vec2, radius: f32, color: Color) {
+    match shape {
+        Shape::Circle => draw_circle(center.x, center.y, radius, color),
+        Shape::Square => draw_rectangle(center.x - radius, center.y - radius, radius * 2.0, radius * 2.0, color),
+        Shape::Triangle => draw_triangle(
+            vec2(center.x, center.y - radius),
+            vec2(center.x - radius, center.y + radius),
+            vec2(center.x + radius, center.y + radius),
+            color,
+        ),
+        Shape::Diamond => draw_poly(center.x, center.y, 4, radius * 1.2, 0f32, color),
+    }
+}
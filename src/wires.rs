@@ -0,0 +1,303 @@
+use crate::input::FrameInput;
+use crate::touch;
+use crate::ui;
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+// Somewhere a wire can be plugged in. Left terminals stick out to the left
+// of their point and right ones to the right
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Terminal {
+    pub at: [f32; 2],
+    pub color: [f32; 3],
+    // Colorblind mode tells terminals apart by these instead of color
+    pub label: String,
+    pub shape: ui::Shape,
+    // Dash and gap lengths of the wire from here, solid if left out
+    #[serde(default)]
+    pub dash: [f32; 2],
+}
+
+impl Terminal {
+    fn point(&self) -> Vec2 {
+        vec2(self.at[0], self.at[1])
+    }
+    fn color(&self) -> Color {
+        Color::new(self.color[0], self.color[1], self.color[2], 1f32)
+    }
+}
+
+// What the puzzle shows for a given wiring, listed by left terminal
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Readout {
+    pub wiring: Vec<Option<usize>>,
+    pub text: String,
+    #[serde(default)]
+    pub flag: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct WireData {
+    lefts: Vec<Terminal>,
+    rights: Vec<Terminal>,
+    terminal_size: [f32; 2],
+    readouts: Vec<Readout>,
+    fallback: String,
+    readout_at: [f32; 2],
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum End {
+    Left(usize),
+    Right(usize),
+}
+
+// Left terminals wired up to right ones. A wire is dragged from one side to
+// the other, or clicked at one end then the other, and right clicking a
+// terminal pulls out whatever is plugged into it
+
+#[derive(Clone)]
+pub struct WirePuzzle {
+    data: WireData,
+    // Which right terminal each left one is wired to
+    pub wires: Vec<Option<usize>>,
+    // Where the wire being placed starts
+    from: Option<End>,
+    mouse: Vec2,
+}
+
+impl WirePuzzle {
+    pub fn new(data: &str) -> Result<Self, String> {
+        let data: WireData = serde_json::from_str(data).map_err(|e| e.to_string())?;
+        Ok(WirePuzzle {
+            wires: vec![None; data.lefts.len()],
+            data,
+            from: None,
+            mouse: vec2(0f32, 0f32),
+        })
+    }
+
+    // Saved wiring, as long as every wire goes between terminals that exist
+    pub fn restore(&mut self, wires: Vec<Option<usize>>) -> Result<(), String> {
+        if wires.len() != self.data.lefts.len() {
+            return Err(format!("{} wires for {} left terminals", wires.len(), self.data.lefts.len()));
+        }
+        if let Some(right) = wires.iter().flatten().find(|r| **r >= self.data.rights.len()) {
+            return Err(format!("there's no right terminal {}", right));
+        }
+        self.wires = wires;
+        self.from = None;
+        Ok(())
+    }
+
+    fn terminal(&self, end: End) -> &Terminal {
+        match end {
+            End::Left(i) => &self.data.lefts[i],
+            End::Right(i) => &self.data.rights[i],
+        }
+    }
+
+    fn area(&self, end: End) -> Rect {
+        let [w, h] = self.data.terminal_size;
+        let at = self.terminal(end).point();
+        match end {
+            End::Left(_) => Rect::new(at.x - w, at.y - h / 2.0, w, h),
+            End::Right(_) => Rect::new(at.x, at.y - h / 2.0, w, h),
+        }
+    }
+
+    fn end_at(&self, at: Vec2, touch: bool) -> Option<End> {
+        let lefts = (0..self.data.lefts.len()).map(End::Left);
+        let rights = (0..self.data.rights.len()).map(End::Right);
+        touch::target(lefts.chain(rights).map(|end| (end, self.area(end))), at, touch)
+    }
+
+    // Each terminal takes one wire, so plugging in replaces what was there
+    fn connect(&mut self, a: End, b: End) -> bool {
+        let (left, right) = match (a, b) {
+            (End::Left(l), End::Right(r)) | (End::Right(r), End::Left(l)) => (l, r),
+            _ => return false,
+        };
+        self.disconnect(End::Right(right));
+        self.wires[left] = Some(right);
+        true
+    }
+
+    fn disconnect(&mut self, end: End) {
+        match end {
+            End::Left(l) => self.wires[l] = None,
+            End::Right(r) => {
+                for wire in self.wires.iter_mut().filter(|w| **w == Some(r)) {
+                    *wire = None;
+                }
+            }
+        }
+    }
+
+    // Drops a wire that's halfway placed
+    pub fn cancel(&mut self) {
+        self.from = None;
+    }
+
+    // Returns whether the wiring changed
+    pub fn update(&mut self, input: &FrameInput) -> bool {
+        self.mouse = vec2(input.mouse.x, input.mouse.y);
+        let hovered = self.end_at(self.mouse, input.touch);
+        let before = self.wires.clone();
+
+        if input.pressed.contains(&MouseButton::Right) {
+            self.from = None;
+            if let Some(end) = hovered {
+                self.disconnect(end);
+            }
+        }
+
+        if input.click().is_some() {
+            self.from = match (self.from, hovered) {
+                (Some(from), Some(to)) if self.connect(from, to) => None,
+                (_, hovered) => hovered,
+            };
+        } else if input.released.contains(&MouseButton::Left) {
+            // Letting go over the same terminal leaves it picked for a click
+            // on the other end, anywhere else finishes or drops the drag
+            if let Some(from) = self.from {
+                match hovered {
+                    Some(to) if to == from => (),
+                    Some(to) => {
+                        self.connect(from, to);
+                        self.from = None;
+                    }
+                    None => self.from = None,
+                }
+            }
+        }
+
+        self.wires != before
+    }
+
+    pub fn readout(&self) -> Option<&Readout> {
+        self.data.readouts.iter().find(|r| r.wiring == self.wires)
+    }
+
+    pub fn draw(&self, colorblind: bool) {
+        if colorblind {
+            self.draw_labels();
+        }
+
+        for (i, (left, wire)) in self.data.lefts.iter().zip(&self.wires).enumerate() {
+            let Some(right) = wire else { continue };
+            let (from, to) = (left.point(), self.data.rights[*right].point());
+            self.draw_wire(Some(i), from, to, colorblind);
+        }
+
+        // Rubber band out to the mouse from wherever the wire started
+
+        if let Some(from) = self.from {
+            let start = self.terminal(from).point();
+            let left = match from {
+                End::Left(i) => Some(i),
+                End::Right(_) => None,
+            };
+            self.draw_wire(left, start, self.mouse, colorblind);
+        }
+
+        let text = match self.readout() {
+            Some(readout) => &readout.text,
+            None => &self.data.fallback,
+        };
+        let [x, y] = self.data.readout_at;
+        draw_text(text, x, y, 50.0, WHITE);
+    }
+
+    // Wires only get their own color and dashes in colorblind mode, a wire
+    // with no left end yet stays plain
+    fn draw_wire(&self, left: Option<usize>, from: Vec2, to: Vec2, colorblind: bool) {
+        match left.map(|i| &self.data.lefts[i]) {
+            Some(terminal) if colorblind => {
+                let [dash, gap] = terminal.dash;
+                ui::dashed_line(from, to, 17.0, dash, gap, BLACK);
+                ui::dashed_line(from, to, 11.0, dash, gap, terminal.color());
+            }
+            _ => draw_line(from.x, from.y, to.x, to.y, 15.0, GRAY),
+        }
+    }
+
+    // Every terminal gets a letter and shape so the colors aren't needed,
+    // and the one a wire is being started from gets outlined
+
+    fn draw_labels(&self) {
+        let lefts = (0..self.data.lefts.len()).map(End::Left);
+        let rights = (0..self.data.rights.len()).map(End::Right);
+        for end in lefts.chain(rights) {
+            let terminal = self.terminal(end);
+            let area = self.area(end);
+            let center = area.center();
+            ui::shape(terminal.shape, center - vec2(12.0, 0.0), 10.0, BLACK);
+            draw_text(&terminal.label, center.x + 4.0, center.y + 10.0, 32.0, BLACK);
+            if self.from == Some(end) {
+                draw_rectangle_lines(area.x - 4.0, area.y - 8.0, area.w + 8.0, area.h + 16.0, 6.0, YELLOW);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pos;
+
+    fn wires() -> WirePuzzle {
+        WirePuzzle::new(&std::fs::read_to_string("assets/levels/room/colormatch.json").unwrap()).unwrap()
+    }
+
+    fn input(x: f32, y: f32, button: MouseButton, pressed: bool) -> FrameInput {
+        let mut input = FrameInput::empty(0);
+        input.mouse = Pos::new(x, y);
+        match pressed {
+            true => input.pressed.push(button),
+            false => input.released.push(button),
+        }
+        input
+    }
+
+    #[test]
+    fn dragging_across_connects_a_wire() {
+        let mut wires = wires();
+        assert!(!wires.update(&input(250f32, 65f32, MouseButton::Left, true)));
+        assert!(wires.update(&input(510f32, 148f32, MouseButton::Left, false)));
+        assert_eq!(wires.wires, vec![Some(1), None, None, None]);
+    }
+
+    #[test]
+    fn clicking_both_ends_connects_a_wire() {
+        let mut wires = wires();
+        wires.update(&input(510f32, 65f32, MouseButton::Left, true));
+        wires.update(&input(510f32, 65f32, MouseButton::Left, false));
+        assert!(wires.update(&input(250f32, 65f32, MouseButton::Left, true)));
+        assert_eq!(wires.wires, vec![Some(0), None, None, None]);
+    }
+
+    #[test]
+    fn right_terminals_take_one_wire() {
+        let mut wires = wires();
+        wires.restore(vec![Some(0), None, None, None]).unwrap();
+        wires.update(&input(270f32, 151f32, MouseButton::Left, true));
+        wires.update(&input(510f32, 65f32, MouseButton::Left, false));
+        assert_eq!(wires.wires, vec![None, Some(0), None, None]);
+
+        assert!(wires.update(&input(510f32, 65f32, MouseButton::Right, true)));
+        assert_eq!(wires.wires, vec![None; 4]);
+    }
+
+    #[test]
+    fn wirings_show_their_readout() {
+        let mut wires = wires();
+        assert!(wires.readout().is_none());
+        wires.restore(vec![Some(1), Some(0), Some(3), Some(2)]).unwrap();
+        assert_eq!(wires.readout().map(|r| r.text.as_str()), Some("1776!"));
+        assert!(wires.restore(vec![Some(4), None, None, None]).is_err());
+        assert!(wires.restore(vec![None]).is_err());
+    }
+}
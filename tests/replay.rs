@@ -0,0 +1,23 @@
+use std::process::Command;
+
+// Plays a recorded session through the real game with no window and checks
+// where it ended up
+
+fn replay(recording: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_gmtk_2023_escape_room"))
+        .args(["--headless", "--replay", recording])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn opening_the_door_and_staying_home() {
+    let out = replay("tests/fixtures/door.rec");
+    assert!(
+        out.lines().any(|line| line == "Replayed 11 frames: state=Complete room=North item=none ending=homebody"),
+        "{}",
+        out
+    );
+}
@@ -0,0 +1,13 @@
+#!/bin/sh
+# Builds the browser version into dist/, ready to be served as static files.
+# Needs the wasm32-unknown-unknown target (rustup target add wasm32-unknown-unknown)
+
+set -e
+cd "$(dirname "$0")/.."
+
+cargo build --release --target wasm32-unknown-unknown
+rm -rf dist
+mkdir -p dist
+cp web/index.html web/mq_js_bundle.js web/storage.js dist/
+cp target/wasm32-unknown-unknown/release/gmtk_2023_escape_room.wasm dist/
+cp -r assets dist/
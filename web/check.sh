@@ -0,0 +1,10 @@
+#!/bin/sh
+# Makes sure the browser version still compiles and passes clippy, without
+# putting a release build together. Worth running before anything that
+# touches storage, networking or other desktop only code
+
+set -e
+cd "$(dirname "$0")/.."
+
+cargo build --target wasm32-unknown-unknown
+cargo clippy --target wasm32-unknown-unknown -- -D warnings
@@ -0,0 +1,37 @@
+// Saves and settings for the browser build, kept in localStorage. Goes with
+// the backend in src/storage.rs
+
+(function () {
+    var encoder = new TextEncoder();
+    var decoder = new TextDecoder();
+
+    function string(ptr, len) {
+        return decoder.decode(new Uint8Array(wasm_memory.buffer, ptr, len));
+    }
+
+    miniquad_add_plugin({
+        name: "storage",
+        version: "0.1.0",
+        register_plugin: function (importObject) {
+            // Length in bytes of what's stored, or -1 if nothing is
+            importObject.env.storage_length = function (key, key_len) {
+                var value = localStorage.getItem(string(key, key_len));
+                return value === null ? -1 : encoder.encode(value).length;
+            };
+            importObject.env.storage_read = function (key, key_len, out) {
+                var bytes = encoder.encode(localStorage.getItem(string(key, key_len)) || "");
+                new Uint8Array(wasm_memory.buffer, out, bytes.length).set(bytes);
+            };
+            importObject.env.storage_write = function (key, key_len, value, value_len) {
+                try {
+                    localStorage.setItem(string(key, key_len), string(value, value_len));
+                } catch (e) {
+                    console.warn("Couldn't save " + string(key, key_len) + ": " + e);
+                }
+            };
+            importObject.env.storage_remove = function (key, key_len) {
+                localStorage.removeItem(string(key, key_len));
+            };
+        },
+    });
+})();